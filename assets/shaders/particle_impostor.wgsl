@@ -0,0 +1,67 @@
+// a particle drawn as a camera-facing quad, shaded and depth tested as the sphere it stands for
+#import bevy_pbr::{
+    mesh_functions::get_world_from_local,
+    mesh_view_bindings::view,
+}
+
+@group(2) @binding(0) var<uniform> color: vec4<f32>;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>, // corner of a quad from -1 to 1
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) view_position: vec3<f32>, // on the quad, in view space
+    @location(1) @interpolate(flat) center: vec3<f32>,
+    @location(2) @interpolate(flat) radius: f32,
+};
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let world_from_local = get_world_from_local(vertex.instance_index);
+    let radius = length(world_from_local[0].xyz);
+    let center = (view.view_from_world * vec4(world_from_local[3].xyz, 1.0)).xyz;
+    // seen from close by the outline of the sphere is wider than its radius
+    let distance = length(center);
+    let grow = distance / sqrt(max(distance * distance - radius * radius, 1e-8));
+    let position = center + vec3(vertex.position.xy * radius * min(grow, 4.0), 0.0);
+
+    var out: VertexOutput;
+    out.clip_position = view.clip_from_view * vec4(position, 1.0);
+    out.view_position = position;
+    out.center = center;
+    out.radius = radius;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> FragmentOutput {
+    // the ray from the eye through the pixel hits the sphere or misses the particle
+    let dir = normalize(in.view_position);
+    let b = dot(dir, in.center);
+    let discriminant = b * b - dot(in.center, in.center) + in.radius * in.radius;
+    if discriminant < 0.0 {
+        discard;
+    }
+    let hit = dir * (b - sqrt(discriminant));
+    let normal = normalize(hit - in.center);
+
+    // a light above and behind the viewer
+    let light = normalize(vec3(0.3, 0.6, 0.8));
+    let diffuse = max(dot(normal, light), 0.0);
+    let half_vector = normalize(light - dir);
+    let specular = 0.3 * pow(max(dot(normal, half_vector), 0.0), 32.0);
+
+    let clip = view.clip_from_view * vec4(hit, 1.0);
+    var out: FragmentOutput;
+    out.color = vec4(color.rgb * (0.2 + 0.8 * diffuse) + specular, 1.0);
+    out.depth = clip.z / clip.w;
+    return out;
+}
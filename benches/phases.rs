@@ -0,0 +1,177 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput};
+
+use pbf_rs::diffuse::DiffuseParams;
+use pbf_rs::params::SimParams;
+use pbf_rs::precision::{Real, Vector, vector};
+use pbf_rs::scene_desc::{FluidBlock, SceneDesc};
+use pbf_rs::simulator::{Phase, Simulator};
+
+// nominal particle counts, the lattice rounds them down a little
+const SIZES: [(&str, usize); 3] = [("10k", 10_000), ("100k", 100_000), ("1M", 1_000_000)];
+// steps before measuring, so the neighbor lists are those of moving fluid, not of the lattice
+const WARMUP_STEPS: usize = 10;
+
+// a dam break holding about the given number of particles
+fn dam_break(particles: usize) -> Simulator {
+    let tank = vector(1.0, 1.0, 0.5);
+    let size = vector(0.5, 0.6, 1.0);
+    // every particle takes a 2r x sqrt(3)r x 2r cell of the lattice
+    let volume = (size * tank).element_product();
+    let radius = (volume / (4.0 * (3.0 as Real).sqrt() * particles as Real)).cbrt();
+    let params = SimParams::builder().radius(radius).build().unwrap();
+    let mut simulator = Simulator::new(params);
+    simulator.set_scene(SceneDesc {
+        name: "Dam Break".to_string(),
+        tank,
+        blocks: vec![FluidBlock {
+            size,
+            offset: Vector::ZERO,
+            material: Default::default(),
+        }],
+        diffuse: Some(DiffuseParams::default()),
+        ..SceneDesc::default()
+    });
+    simulator.reset_system();
+    let dt = simulator.params().time_step();
+    for _ in 0..WARMUP_STEPS {
+        simulator.simulate_timestep(dt);
+    }
+    simulator
+}
+
+// the simulator right before the first time the phase runs in a step
+fn before(simulator: &Simulator, phase: Phase) -> Simulator {
+    let mut simulator = simulator.clone();
+    let dt = simulator.params().time_step();
+    for p in simulator
+        .step_phases()
+        .into_iter()
+        .take_while(|&p| p != phase)
+    {
+        simulator.run_phase(p, dt);
+    }
+    simulator
+}
+
+// a benchmark that ran, with the actual particle count
+struct Run {
+    group: &'static str,
+    size: &'static str,
+    particles: usize,
+}
+
+fn bench_phases(criterion: &mut Criterion) -> Vec<Run> {
+    let mut runs = Vec::new();
+    for (size, particles) in SIZES {
+        // scenes are only set up for the sizes that pass the command line filter
+        let mut scene: Option<Simulator> = None;
+        let groups = Phase::ALL.map(|phase| (phase.name(), Some(phase)));
+        for (group, phase) in groups.into_iter().chain([("step", None)]) {
+            let mut state: Option<Simulator> = None;
+            let mut group = criterion.benchmark_group(group);
+            group.sample_size(10);
+            group.throughput(Throughput::Elements(particles as u64));
+            group.bench_function(BenchmarkId::from_parameter(size), |b| {
+                let state = state.get_or_insert_with(|| {
+                    let scene = scene.get_or_insert_with(|| dam_break(particles));
+                    runs.push(Run {
+                        group: phase.map_or("step", Phase::name),
+                        size,
+                        particles: scene.num_sphere,
+                    });
+                    match phase {
+                        Some(phase) => before(scene, phase),
+                        None => scene.clone(),
+                    }
+                });
+                let dt = state.params().time_step();
+                b.iter_batched(
+                    || state.clone(),
+                    |mut simulator| {
+                        match phase {
+                            Some(phase) => simulator.run_phase(phase, dt),
+                            None => simulator.simulate_timestep(dt),
+                        }
+                        // dropped outside the measurement
+                        simulator
+                    },
+                    BatchSize::LargeInput,
+                );
+            });
+            group.finish();
+        }
+    }
+    runs
+}
+
+// where criterion keeps its results
+fn criterion_home() -> PathBuf {
+    if let Some(home) = env::var_os("CRITERION_HOME") {
+        return PathBuf::from(home);
+    }
+    let target = env::var_os("CARGO_TARGET_DIR").map_or_else(|| "target".into(), PathBuf::from);
+    target.join("criterion")
+}
+
+// one line per benchmark, the rows of this run replace the ones with the same phase and
+// size and the rows of benchmarks it filtered out are kept, to compare between commits
+fn write_summary(runs: &[Run]) -> io::Result<PathBuf> {
+    let home = criterion_home();
+    let path = home.join("summary.csv");
+    let header = "phase,size,particles,mean_ns,median_ns,std_dev_ns";
+    let mut rows: Vec<String> = match fs::read_to_string(&path) {
+        Ok(text) => text.lines().skip(1).map(str::to_string).collect(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(err) => return Err(err),
+    };
+    for run in runs {
+        let estimates_path = home
+            .join(run.group)
+            .join(run.size)
+            .join("new/estimates.json");
+        let estimates: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&estimates_path)?)?;
+        let estimate = |name: &str| {
+            estimates[name]["point_estimate"]
+                .as_f64()
+                .unwrap_or(f64::NAN)
+        };
+        let key = format!("{},{},", run.group, run.size);
+        let row = format!(
+            "{key}{},{:.0},{:.0},{:.0}",
+            run.particles,
+            estimate("mean"),
+            estimate("median"),
+            estimate("std_dev")
+        );
+        match rows.iter_mut().find(|row| row.starts_with(&key)) {
+            Some(old) => *old = row,
+            None => rows.push(row),
+        }
+    }
+    let mut csv = format!("{header}\n");
+    for row in rows {
+        csv += &row;
+        csv.push('\n');
+    }
+    fs::write(&path, csv)?;
+    Ok(path)
+}
+
+fn main() {
+    let mut criterion = Criterion::default().configure_from_args();
+    let runs = bench_phases(&mut criterion);
+    criterion.final_summary();
+    if runs.is_empty() {
+        return;
+    }
+    match write_summary(&runs) {
+        Ok(path) => println!("summary written to {}", path.display()),
+        Err(err) => eprintln!("failed to write the summary: {err}"),
+    }
+}
@@ -0,0 +1,380 @@
+// headless runner for batch simulations, steps a scene without rendering and writes
+// particle frames, stats and checkpoints to disk
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+
+use pbf_rs::cache::{CacheError, CacheOptions, CacheWriter};
+use pbf_rs::checkpoint::Checkpoint;
+use pbf_rs::params::SimParams;
+use pbf_rs::precision::Real;
+use pbf_rs::scene_desc::SceneDesc;
+use pbf_rs::simulator::Simulator;
+
+const USAGE: &str = "\
+usage: pbf-cli <scene> (--steps N | --seconds S) [options]
+       pbf-cli --resume <checkpoint> (--steps N | --seconds S) [options]
+
+<scene> is a .ron scene file or the name of a builtin scene.
+
+options:
+  --steps N             time steps to run
+  --seconds S           simulated time to run
+  --radius R            particle radius
+  --time-step DT        time step in seconds
+  --out DIR             output directory [default: output]
+  --frame-every N       write particle frames every N steps, 0 for none [default: 10]
+  --cache FILE          write the particle frames to a particle cache instead of PLY files
+  --checkpoint-every N  write a checkpoint every N steps, 0 for the last step only [default: 0]
+  --resume FILE         continue from a checkpoint
+  --quiet               no progress output
+
+exit codes: 0 success, 1 the simulation failed, 2 bad arguments or files";
+
+// seconds between progress lines
+const PROGRESS_INTERVAL: f64 = 1.0;
+
+struct Options {
+    scene: Option<String>,
+    resume: Option<PathBuf>,
+    steps: Option<usize>,
+    seconds: Option<Real>,
+    radius: Option<Real>,
+    time_step: Option<Real>,
+    out: PathBuf,
+    frame_every: usize,
+    cache: Option<PathBuf>,
+    checkpoint_every: usize,
+    quiet: bool,
+}
+
+// why a run stopped early, decides the exit code
+enum Failure {
+    Setup(String),
+    Simulation(String),
+}
+
+fn value<T: std::str::FromStr>(
+    name: &str,
+    args: &mut impl Iterator<Item = String>,
+) -> Result<T, String> {
+    let arg = args.next().ok_or_else(|| format!("{name} needs a value"))?;
+    arg.parse()
+        .map_err(|_| format!("invalid value for {name}: {arg}"))
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        scene: None,
+        resume: None,
+        steps: None,
+        seconds: None,
+        radius: None,
+        time_step: None,
+        out: PathBuf::from("output"),
+        frame_every: 10,
+        cache: None,
+        checkpoint_every: 0,
+        quiet: false,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--steps" => options.steps = Some(value(&arg, &mut args)?),
+            "--seconds" => options.seconds = Some(value(&arg, &mut args)?),
+            "--radius" => options.radius = Some(value(&arg, &mut args)?),
+            "--time-step" => options.time_step = Some(value(&arg, &mut args)?),
+            "--out" => options.out = value(&arg, &mut args)?,
+            "--frame-every" => options.frame_every = value(&arg, &mut args)?,
+            "--cache" => options.cache = Some(value(&arg, &mut args)?),
+            "--checkpoint-every" => options.checkpoint_every = value(&arg, &mut args)?,
+            "--resume" => options.resume = Some(value(&arg, &mut args)?),
+            "--quiet" => options.quiet = true,
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ if options.scene.is_none() => options.scene = Some(arg),
+            _ => return Err(format!("unexpected argument {arg}")),
+        }
+    }
+    match (&options.scene, &options.resume) {
+        (None, None) => return Err("no scene given".to_string()),
+        (Some(_), Some(_)) => {
+            return Err("a resumed run takes its scene from the checkpoint".to_string());
+        }
+        (None, Some(_)) if options.radius.is_some() => {
+            return Err("the radius of a resumed run is fixed by the checkpoint".to_string());
+        }
+        _ => {}
+    }
+    match (options.steps, options.seconds) {
+        (None, None) => {
+            return Err("give the length of the run with --steps or --seconds".to_string());
+        }
+        (Some(_), Some(_)) => return Err("--steps and --seconds exclude each other".to_string()),
+        _ => {}
+    }
+    if options.cache.is_some() && options.frame_every == 0 {
+        return Err("--cache needs particle frames, --frame-every must not be 0".to_string());
+    }
+    Ok(Some(options))
+}
+
+// a scene file, or a builtin scene by name
+fn find_scene(name: &str) -> Result<SceneDesc, String> {
+    let path = Path::new(name);
+    if path.extension().is_some_and(|ext| ext == "ron") || path.is_file() {
+        return SceneDesc::load(path).map_err(|err| format!("{}: {err}", path.display()));
+    }
+    let builtin = SceneDesc::builtin();
+    let names: Vec<_> = builtin.iter().map(|scene| scene.name.clone()).collect();
+    builtin
+        .into_iter()
+        .find(|scene| scene.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            format!("no scene file or builtin scene {name:?}, builtin scenes are {names:?}")
+        })
+}
+
+fn setup(options: &Options) -> Result<Simulator, String> {
+    let mut simulator = Simulator::new(SimParams::default());
+    let mut builder = match (&options.scene, &options.resume) {
+        (Some(scene), _) => {
+            simulator.set_scene(find_scene(scene)?);
+            let mut builder = SimParams::builder();
+            if let Some(radius) = options.radius {
+                builder = builder.radius(radius);
+            }
+            builder
+        }
+        (None, Some(path)) => {
+            let checkpoint =
+                Checkpoint::load(path).map_err(|err| format!("{}: {err}", path.display()))?;
+            simulator
+                .restore(checkpoint)
+                .map_err(|err| format!("{}: {err}", path.display()))?;
+            simulator.params().to_builder()
+        }
+        (None, None) => unreachable!(),
+    };
+    if let Some(time_step) = options.time_step {
+        builder = builder.time_step(time_step);
+    }
+    let params = builder.build().map_err(|err| err.to_string())?;
+    simulator.set_params(params);
+    if options.resume.is_none() {
+        simulator.reset_system();
+        // densities for the stats of the initial state
+        simulator.find_neighbors();
+    }
+    Ok(simulator)
+}
+
+// the first particle that blew up or escaped the tank
+fn check(simulator: &Simulator) -> Result<(), String> {
+    let half = 0.5 * simulator.tank + simulator.neighbor_radius();
+    let particles = simulator.position.iter().zip(simulator.velocity());
+    for (i, (&p, &v)) in particles.enumerate() {
+        if !p.is_finite() || !v.is_finite() {
+            return Err(format!(
+                "particle {i} has a non-finite position or velocity"
+            ));
+        }
+        if p.abs().cmpgt(half).any() {
+            return Err(format!("particle {i} left the tank at {p}"));
+        }
+    }
+    Ok(())
+}
+
+fn write_stats_header(stats: &mut impl Write) -> io::Result<()> {
+    writeln!(
+        stats,
+        "step,time,particles,max_speed,kinetic_energy,mean_compression,diffuse"
+    )
+}
+
+// one line of stats.csv, the kinetic energy is per unit particle mass
+fn write_stats(stats: &mut impl Write, step: usize, simulator: &Simulator) -> io::Result<()> {
+    let speed = simulator.velocity().iter().map(|v| v.length());
+    let max_speed = speed.fold(0.0 as Real, Real::max);
+    let kinetic_energy: Real = simulator
+        .velocity()
+        .iter()
+        .map(|v| 0.5 * v.length_squared())
+        .sum();
+    let density = simulator.relative_density();
+    let compression =
+        density.iter().map(|d| (d - 1.0).max(0.0)).sum::<Real>() / density.len().max(1) as Real;
+    let diffuse = simulator
+        .diffuse()
+        .map_or(0, |diffuse| diffuse.particles.len());
+    writeln!(
+        stats,
+        "{step},{},{},{max_speed},{kinetic_energy},{compression},{diffuse}",
+        simulator.time, simulator.num_sphere
+    )
+}
+
+// the particles are left out when they go to a cache
+fn write_frame(dir: &Path, step: usize, simulator: &Simulator, particles: bool) -> io::Result<()> {
+    if particles {
+        let path = dir.join(format!("particles_{step:06}.ply"));
+        simulator.write_ply(BufWriter::new(File::create(path)?))?;
+    }
+    if let Some(diffuse) = simulator.diffuse() {
+        let path = dir.join(format!("diffuse_{step:06}.ply"));
+        diffuse.write_ply(BufWriter::new(File::create(path)?))?;
+    }
+    Ok(())
+}
+
+fn run(options: &Options) -> Result<(), Failure> {
+    let mut simulator = setup(options).map_err(Failure::Setup)?;
+    let dt = simulator.params().time_step();
+    let steps = match (options.steps, options.seconds) {
+        (Some(steps), _) => steps,
+        (None, Some(seconds)) => (seconds / dt).round() as usize,
+        (None, None) => unreachable!(),
+    };
+    // a resumed run numbers its steps on from the checkpoint
+    let first = (simulator.time / dt).round() as usize;
+    let last = first + steps;
+
+    let io_error = |what: &str, path: &Path, err: io::Error| {
+        Failure::Setup(format!("failed to write {what} {}: {err}", path.display()))
+    };
+    let frames = options.out.join("frames");
+    let checkpoints = options.out.join("checkpoints");
+    for dir in [&frames, &checkpoints] {
+        fs::create_dir_all(dir).map_err(|err| io_error("directory", dir, err))?;
+    }
+    let stats_path = options.out.join("stats.csv");
+    let mut stats = File::create(&stats_path)
+        .map(BufWriter::new)
+        .and_then(|mut stats| write_stats_header(&mut stats).map(|_| stats))
+        .map_err(|err| io_error("stats", &stats_path, err))?;
+    let cache_error = |err: CacheError| {
+        let path = options.cache.as_deref().unwrap_or(Path::new(""));
+        Failure::Setup(format!("{}: {err}", path.display()))
+    };
+    // over the tank with the particles on its walls
+    let half = 0.5 * simulator.tank + simulator.radius;
+    let mut cache = options
+        .cache
+        .as_ref()
+        .map(|path| {
+            let options = CacheOptions::default();
+            CacheWriter::create(path, -half, half, simulator.num_sphere, options)
+        })
+        .transpose()
+        .map_err(cache_error)?;
+    let save_checkpoint = |step: usize, simulator: &Simulator| {
+        let path = checkpoints.join(format!("checkpoint_{step:06}.ron"));
+        simulator
+            .checkpoint()
+            .save(&path)
+            .map_err(|err| Failure::Setup(format!("{}: {err}", path.display())))
+    };
+
+    if !options.quiet {
+        eprintln!(
+            "{}: {} particles, {steps} steps of {dt} s",
+            simulator.scene().name,
+            simulator.num_sphere
+        );
+    }
+    let start = Instant::now();
+    let mut last_progress = start;
+    let mut step = first;
+    loop {
+        if options.frame_every > 0 && step.is_multiple_of(options.frame_every) {
+            if let Some(cache) = &mut cache {
+                cache
+                    .write_frame(simulator.time, &simulator.position)
+                    .map_err(cache_error)?;
+            }
+            write_frame(&frames, step, &simulator, cache.is_none())
+                .map_err(|err| io_error("frame in", &frames, err))?;
+        }
+        write_stats(&mut stats, step, &simulator)
+            .map_err(|err| io_error("stats", &stats_path, err))?;
+        if step == last {
+            break;
+        }
+
+        simulator.simulate_timestep(dt);
+        step += 1;
+        if let Err(err) = check(&simulator) {
+            // keep what led up to the failure
+            let _ = write_stats(&mut stats, step, &simulator);
+            let _ = stats.flush();
+            let _ = cache.map(CacheWriter::finish);
+            return Err(Failure::Simulation(format!(
+                "step {step}, t = {:.4} s: {err}",
+                simulator.time
+            )));
+        }
+        if options.checkpoint_every > 0
+            && step.is_multiple_of(options.checkpoint_every)
+            && step != last
+        {
+            save_checkpoint(step, &simulator)?;
+        }
+
+        let now = Instant::now();
+        if !options.quiet && (now - last_progress).as_secs_f64() >= PROGRESS_INTERVAL {
+            last_progress = now;
+            let done = step - first;
+            let rate = done as f64 / (now - start).as_secs_f64();
+            eprintln!(
+                "step {step}/{last}  t = {:.3} s  {rate:.1} steps/s  eta {:.0} s",
+                simulator.time,
+                (last - step) as f64 / rate
+            );
+        }
+    }
+    save_checkpoint(last, &simulator)?;
+    cache
+        .map(CacheWriter::finish)
+        .transpose()
+        .map_err(cache_error)?;
+    stats
+        .flush()
+        .map_err(|err| io_error("stats", &stats_path, err))?;
+
+    if !options.quiet {
+        let elapsed = start.elapsed().as_secs_f64();
+        eprintln!(
+            "done: {steps} steps in {elapsed:.1} s, output in {}",
+            options.out.display()
+        );
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Setup(err)) => {
+            eprintln!("error: {err}");
+            ExitCode::from(2)
+        }
+        Err(Failure::Simulation(err)) => {
+            eprintln!("simulation failed at {err}");
+            ExitCode::from(1)
+        }
+    }
+}
@@ -0,0 +1,377 @@
+use serde::{Deserialize, Serialize};
+
+use crate::motion::{Motion, Pose};
+use crate::precision::{Real, Vector, vector};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Shape {
+    Plane { normal: Vector }, // particles stay on the side the normal points to
+    Box { half_extents: Vector },
+    Sphere { radius: Real },
+}
+
+impl Shape {
+    // push a particle of the given radius out of the shape placed at pose
+    // returns the corrected position and the contact normal
+    pub fn project(&self, pose: Pose, p: Vector, radius: Real) -> Option<(Vector, Vector)> {
+        match self {
+            Shape::Plane { normal } => {
+                let n = pose.rotation * normal.normalize();
+                let dist = (p - pose.translation).dot(n) - radius;
+                (dist < 0.0).then(|| (p - dist * n, n))
+            }
+            Shape::Sphere { radius: r } => {
+                let d = p - pose.translation;
+                let len = d.length();
+                let min = r + radius;
+                if len >= min {
+                    return None;
+                }
+                let n = if len > 1e-12 { d / len } else { Vector::Y };
+                Some((pose.translation + n * min, n))
+            }
+            Shape::Box { half_extents } => {
+                let q = pose.inverse_transform_point(p);
+                let e = *half_extents + Vector::splat(radius);
+                let depth = e - q.abs();
+                if depth.min_element() <= 0.0 {
+                    return None;
+                }
+                // leave through the face with the smallest penetration
+                let axis = if depth.x <= depth.y && depth.x <= depth.z {
+                    Vector::X
+                } else if depth.y <= depth.z {
+                    Vector::Y
+                } else {
+                    Vector::Z
+                };
+                let sign = if q.dot(axis) < 0.0 { -1.0 } else { 1.0 };
+                let q = q + axis * depth.dot(axis) * sign;
+                Some((pose.transform_point(q), pose.rotation * (axis * sign)))
+            }
+        }
+    }
+
+    // points on the surface in the shape frame, roughly spacing apart
+    // planes are sampled on a square large enough to cut the whole tank
+    pub fn sample_surface(&self, spacing: Real, tank: Vector) -> Vec<Vector> {
+        match self {
+            Shape::Plane { normal } => {
+                let n = normal.normalize();
+                let u = n.any_orthonormal_vector();
+                let v = n.cross(u);
+                let half = tank.length();
+                let count = (2.0 * half / spacing).ceil() as usize;
+                let step = 2.0 * half / count as Real;
+                let mut points = Vec::with_capacity((count + 1) * (count + 1));
+                for i in 0..=count {
+                    for j in 0..=count {
+                        let a = -half + step * i as Real;
+                        let b = -half + step * j as Real;
+                        points.push(a * u + b * v);
+                    }
+                }
+                points
+            }
+            Shape::Box { half_extents } => box_surface(*half_extents, spacing),
+            Shape::Sphere { radius } => {
+                // fibonacci lattice
+                let count = ((4.0 * std::f64::consts::PI as Real * radius * radius)
+                    / (spacing * spacing))
+                    .ceil()
+                    .max(1.0) as usize;
+                let golden = std::f64::consts::PI as Real * (3.0 - (5.0 as Real).sqrt());
+                (0..count)
+                    .map(|k| {
+                        let y = 1.0 - 2.0 * (k as Real + 0.5) / count as Real;
+                        let r = (1.0 - y * y).sqrt();
+                        let (s, c) = (golden * k as Real).sin_cos();
+                        vector(r * c, y, r * s) * *radius
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    // line segments outlining the shape placed at pose, planes are clipped to the tank
+    pub fn wireframe(&self, pose: Pose, tank: Vector) -> Vec<[Vector; 2]> {
+        match self {
+            Shape::Plane { normal } => {
+                let n = pose.rotation * normal.normalize();
+                plane_outline(pose.translation, n, 0.5 * tank)
+            }
+            Shape::Box { half_extents } => box_edges(*half_extents)
+                .iter()
+                .map(|[a, b]| [pose.transform_point(*a), pose.transform_point(*b)])
+                .collect(),
+            Shape::Sphere { radius } => {
+                const SEGMENTS: usize = 32;
+                let mut lines = Vec::with_capacity(3 * SEGMENTS);
+                for axis in 0..3 {
+                    let point = |k: usize| {
+                        let a = k as Real / SEGMENTS as Real * 2.0 * std::f64::consts::PI as Real;
+                        let (s, c) = a.sin_cos();
+                        let local = match axis {
+                            0 => vector(0.0, c, s),
+                            1 => vector(c, 0.0, s),
+                            _ => vector(c, s, 0.0),
+                        };
+                        pose.transform_point(local * *radius)
+                    };
+                    for k in 0..SEGMENTS {
+                        lines.push([point(k), point(k + 1)]);
+                    }
+                }
+                lines
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SlipMode {
+    // the tangential velocity is only reduced by friction
+    #[default]
+    FreeSlip,
+    // touching particles move along with the surface
+    NoSlip,
+}
+
+// how a surface responds to the particles touching it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Surface {
+    pub friction: Real,    // coulomb friction coefficient
+    pub restitution: Real, // fraction of the approach speed particles bounce back with
+    pub slip: SlipMode,
+}
+
+// a wall or collider inside the tank, moved by its motion curves
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "BoundaryDesc", into = "BoundaryDesc")]
+pub struct Boundary {
+    pub shape: Shape,
+    pub motion: Motion,
+    pub surface: Surface,
+    pose: Pose,
+    prev_pose: Pose,
+    dt: Real,
+}
+
+// the serialized part of a boundary, the pose follows from the motion
+#[derive(Serialize, Deserialize)]
+struct BoundaryDesc {
+    shape: Shape,
+    #[serde(default)]
+    motion: Motion,
+    #[serde(default)]
+    surface: Surface,
+}
+
+impl From<BoundaryDesc> for Boundary {
+    fn from(desc: BoundaryDesc) -> Self {
+        Boundary::new(desc.shape, desc.motion).with_surface(desc.surface)
+    }
+}
+
+impl From<Boundary> for BoundaryDesc {
+    fn from(boundary: Boundary) -> Self {
+        Self {
+            shape: boundary.shape,
+            motion: boundary.motion,
+            surface: boundary.surface,
+        }
+    }
+}
+
+impl Boundary {
+    pub fn new(shape: Shape, motion: Motion) -> Self {
+        let pose = motion.pose(0.0);
+        Self {
+            shape,
+            motion,
+            surface: Surface::default(),
+            pose,
+            prev_pose: pose,
+            dt: 0.0,
+        }
+    }
+
+    pub fn with_surface(mut self, surface: Surface) -> Self {
+        self.surface = surface;
+        self
+    }
+
+    pub fn pose(&self) -> Pose {
+        self.pose
+    }
+
+    // move the boundary to time t, dt is the step that took it there
+    pub fn update(&mut self, t: Real, dt: Real) {
+        self.prev_pose = self.pose;
+        self.pose = self.motion.pose(t);
+        self.dt = dt;
+    }
+
+    pub fn reset(&mut self) {
+        self.pose = self.motion.pose(0.0);
+        self.prev_pose = self.pose;
+        self.dt = 0.0;
+    }
+
+    // velocity of the boundary surface point at p during the last step
+    pub fn velocity_at(&self, p: Vector) -> Vector {
+        if self.dt <= 0.0 {
+            return Vector::ZERO;
+        }
+        let local = self.pose.inverse_transform_point(p);
+        (p - self.prev_pose.transform_point(local)) / self.dt
+    }
+
+    // push a particle of the given radius out of the boundary
+    // returns the corrected position and the contact normal
+    pub fn project(&self, p: Vector, radius: Real) -> Option<(Vector, Vector)> {
+        self.shape.project(self.pose, p, radius)
+    }
+
+    // points on the surface in the boundary frame, roughly spacing apart
+    pub fn sample_surface(&self, spacing: Real, tank: Vector) -> Vec<Vector> {
+        self.shape.sample_surface(spacing, tank)
+    }
+
+    // line segments outlining the boundary, planes are clipped to the tank
+    pub fn wireframe(&self, tank: Vector) -> Vec<[Vector; 2]> {
+        self.shape.wireframe(self.pose, tank)
+    }
+}
+
+// points on the faces of an axis aligned box centered at the origin, roughly spacing apart
+pub fn box_surface(half: Vector, spacing: Real) -> Vec<Vector> {
+    let mut points = Vec::new();
+    for axis in 0..3 {
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let count_u = (2.0 * half[u] / spacing).ceil().max(1.0) as usize;
+        let count_v = (2.0 * half[v] / spacing).ceil().max(1.0) as usize;
+        for sign in [-1.0, 1.0] {
+            for i in 0..=count_u {
+                for j in 0..=count_v {
+                    let mut p = Vector::ZERO;
+                    p[axis] = sign * half[axis];
+                    p[u] = -half[u] + 2.0 * half[u] * i as Real / count_u as Real;
+                    p[v] = -half[v] + 2.0 * half[v] * j as Real / count_v as Real;
+                    points.push(p);
+                }
+            }
+        }
+    }
+    points
+}
+
+// the 12 edges of an axis aligned box centered at the origin
+pub fn box_edges(half: Vector) -> [[Vector; 2]; 12] {
+    let c = |x: Real, y: Real, z: Real| vector(x * half.x, y * half.y, z * half.z);
+    [
+        [c(-1.0, -1.0, -1.0), c(1.0, -1.0, -1.0)],
+        [c(1.0, -1.0, -1.0), c(1.0, 1.0, -1.0)],
+        [c(1.0, 1.0, -1.0), c(-1.0, 1.0, -1.0)],
+        [c(-1.0, 1.0, -1.0), c(-1.0, -1.0, -1.0)],
+        [c(-1.0, -1.0, 1.0), c(1.0, -1.0, 1.0)],
+        [c(1.0, -1.0, 1.0), c(1.0, 1.0, 1.0)],
+        [c(1.0, 1.0, 1.0), c(-1.0, 1.0, 1.0)],
+        [c(-1.0, 1.0, 1.0), c(-1.0, -1.0, 1.0)],
+        [c(-1.0, -1.0, -1.0), c(-1.0, -1.0, 1.0)],
+        [c(1.0, -1.0, -1.0), c(1.0, -1.0, 1.0)],
+        [c(1.0, 1.0, -1.0), c(1.0, 1.0, 1.0)],
+        [c(-1.0, 1.0, -1.0), c(-1.0, 1.0, 1.0)],
+    ]
+}
+
+// polygon where the plane through c with normal n cuts the box of the given half size
+fn plane_outline(c: Vector, n: Vector, half: Vector) -> Vec<[Vector; 2]> {
+    let mut points: Vec<Vector> = Vec::new();
+    for [a, b] in box_edges(half) {
+        let da = (a - c).dot(n);
+        let db = (b - c).dot(n);
+        if (da < 0.0) == (db < 0.0) && da != 0.0 {
+            continue;
+        }
+        let p = if da == db {
+            a
+        } else {
+            a + (b - a) * (da / (da - db))
+        };
+        if points.iter().all(|q| q.distance_squared(p) > 1e-10) {
+            points.push(p);
+        }
+    }
+    if points.len() < 3 {
+        return Vec::new();
+    }
+
+    // order the points by angle around their centroid
+    let center = points.iter().copied().sum::<Vector>() / points.len() as Real;
+    let u = n.any_orthonormal_vector();
+    let v = n.cross(u);
+    points.sort_by(|a, b| {
+        let (da, db) = (*a - center, *b - center);
+        let angle_a = da.dot(v).atan2(da.dot(u));
+        let angle_b = db.dot(v).atan2(db.dot(u));
+        angle_a.total_cmp(&angle_b)
+    });
+    (0..points.len())
+        .map(|i| [points[i], points[(i + 1) % points.len()]])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::motion::{Interpolation, Keyframe, Track};
+
+    #[test]
+    fn velocity_follows_the_motion() {
+        let (amplitude, frequency) = (vector(0.2, 0.1, 0.0), 0.5);
+        let spin = 1.5;
+        let mut boundary = Boundary::new(
+            Shape::Sphere { radius: 0.1 },
+            Motion {
+                translation: Track::Sinusoid {
+                    offset: Vector::ZERO,
+                    amplitude,
+                    frequency,
+                    phase: 0.0,
+                },
+                rotation: Track::Keyframes {
+                    keys: vec![
+                        Keyframe::new(0.0, Vector::ZERO),
+                        Keyframe::new(10.0, vector(0.0, 0.0, 10.0 * spin)),
+                    ],
+                    interpolation: Interpolation::Linear,
+                    looping: false,
+                },
+            },
+        );
+        assert_eq!(boundary.velocity_at(Vector::X), Vector::ZERO);
+
+        let dt = 1e-3;
+        let tau = 2.0 * std::f64::consts::PI as Real;
+        for step in 1..=2000 {
+            let t = step as Real * dt;
+            boundary.update(t, dt);
+            if step % 500 != 0 {
+                continue;
+            }
+            // the translation at the middle of the step plus the spin around z
+            let mid = t - 0.5 * dt;
+            let center = boundary.pose().translation;
+            let arm = boundary.pose().rotation * Vector::X;
+            let expected = amplitude * tau * frequency * (tau * frequency * mid).cos()
+                + Vector::Z.cross(arm) * spin;
+            let velocity = boundary.velocity_at(center + arm);
+            assert!(
+                (velocity - expected).length() < 1e-2,
+                "{velocity} {expected} at {t}"
+            );
+        }
+    }
+}
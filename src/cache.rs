@@ -0,0 +1,499 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::precision::{Real, Vector, vector};
+
+// particle frames on disk, little endian:
+//   header  "PBFC", version u16, bits u8, 0u8, keyframe interval u32, particles u64,
+//           min and max of the bounds 3 x f64 each
+//   frames  kind u8, then per particle and axis a varint: the quantized position in a
+//           keyframe, the zigzag encoded difference from the predicted position otherwise
+//   index   offset u64 and time f64 of every frame
+//   footer  frame count u64, index offset u64, "PBFI"
+const MAGIC: &[u8; 4] = b"PBFC";
+const INDEX_MAGIC: &[u8; 4] = b"PBFI";
+const VERSION: u16 = 1;
+const HEADER_BYTES: usize = 4 + 2 + 2 + 4 + 8 + 6 * 8;
+const FOOTER_BYTES: usize = 8 + 8 + 4;
+const KEYFRAME: u8 = 0;
+const DELTA: u8 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CacheOptions {
+    pub bits: u8, // quantization levels per axis are 2^bits
+    // every this many frames one is stored whole, bounds the frames decoded for a random access
+    pub keyframe_interval: u32,
+}
+
+impl Default for CacheOptions {
+    fn default() -> Self {
+        Self {
+            bits: 16,
+            keyframe_interval: 32,
+        }
+    }
+}
+
+// the file stores f64, the solver may run in single precision
+#[allow(clippy::unnecessary_cast)]
+fn real(x: f64) -> Real {
+    x as Real
+}
+
+#[allow(clippy::unnecessary_cast)]
+fn wide(x: Real) -> f64 {
+    x as f64
+}
+
+// maps positions within the bounds to integers on a regular grid
+#[derive(Clone, Copy, Debug)]
+struct Quantizer {
+    min: [f64; 3],
+    max: [f64; 3],
+    step: [f64; 3],
+    levels: u32, // largest quantized value
+}
+
+impl Quantizer {
+    fn new(min: [f64; 3], max: [f64; 3], bits: u8) -> Self {
+        let levels = ((1u64 << bits) - 1) as u32;
+        Self {
+            min,
+            max,
+            step: [0, 1, 2].map(|axis| (max[axis] - min[axis]) / levels as f64),
+            levels,
+        }
+    }
+
+    // positions outside the bounds are clamped to them
+    fn quantize(&self, p: Vector) -> [u32; 3] {
+        [0, 1, 2].map(|axis| {
+            let t = (wide(p[axis]) - self.min[axis]) / self.step[axis];
+            t.round().clamp(0.0, self.levels as f64) as u32
+        })
+    }
+
+    fn dequantize(&self, q: [u32; 3]) -> Vector {
+        let [x, y, z] =
+            [0, 1, 2].map(|axis| real(self.min[axis] + q[axis] as f64 * self.step[axis]));
+        vector(x, y, z)
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut x: u64) {
+    while x >= 0x80 {
+        out.push(x as u8 | 0x80);
+        x >>= 7;
+    }
+    out.push(x as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<u64, CacheError> {
+    let mut x = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = *data
+            .get(*pos)
+            .ok_or(CacheError::Corrupt("frame ends early"))?;
+        *pos += 1;
+        x |= u64::from(byte & 0x7f) << shift;
+        if byte < 0x80 {
+            return Ok(x);
+        }
+    }
+    Err(CacheError::Corrupt("varint too long"))
+}
+
+// small changes of either sign become small unsigned numbers
+fn zigzag(x: i64) -> u64 {
+    ((x << 1) ^ (x >> 63)) as u64
+}
+
+fn unzigzag(x: u64) -> i64 {
+    (x >> 1) as i64 ^ -((x & 1) as i64)
+}
+
+fn read_bytes<const N: usize>(data: &[u8], pos: usize) -> Result<[u8; N], CacheError> {
+    data.get(pos..pos + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(CacheError::Corrupt("file ends early"))
+}
+
+fn read_u64(data: &[u8], pos: usize) -> Result<u64, CacheError> {
+    read_bytes(data, pos).map(u64::from_le_bytes)
+}
+
+fn read_f64(data: &[u8], pos: usize) -> Result<f64, CacheError> {
+    read_bytes(data, pos).map(f64::from_le_bytes)
+}
+
+// quantized positions of the last two frames since the last keyframe
+// a frame after a keyframe is predicted to stay where it was, later ones to move on with
+// constant velocity, so a falling particle needs about as few bits as a resting one
+#[derive(Clone, Debug, Default)]
+struct Track {
+    last: Vec<[u32; 3]>,
+    before: Vec<[u32; 3]>,
+    frames: usize, // since the last keyframe, including it
+}
+
+impl Track {
+    fn predict(&self, particle: usize, axis: usize) -> i64 {
+        let last = i64::from(self.last[particle][axis]);
+        if self.frames < 2 {
+            last
+        } else {
+            2 * last - i64::from(self.before[particle][axis])
+        }
+    }
+
+    fn push(&mut self, frame: Vec<[u32; 3]>, keyframe: bool) {
+        self.frames = if keyframe { 1 } else { self.frames + 1 };
+        self.before = std::mem::replace(&mut self.last, frame);
+    }
+}
+
+// writes frames as they are simulated, the index follows on finish
+pub struct CacheWriter<W: Write> {
+    writer: W,
+    quantizer: Quantizer,
+    keyframe_interval: u32,
+    particles: usize,
+    track: Track,
+    chunk: Vec<u8>,
+    index: Vec<(u64, f64)>,
+    offset: u64,
+}
+
+impl CacheWriter<BufWriter<File>> {
+    pub fn create(
+        path: impl AsRef<Path>,
+        min: Vector,
+        max: Vector,
+        particles: usize,
+        options: CacheOptions,
+    ) -> Result<Self, CacheError> {
+        let file = File::create(path).map_err(CacheError::Io)?;
+        Self::new(BufWriter::new(file), min, max, particles, options)
+    }
+}
+
+impl<W: Write> CacheWriter<W> {
+    // positions are quantized within the box from min to max
+    pub fn new(
+        mut writer: W,
+        min: Vector,
+        max: Vector,
+        particles: usize,
+        options: CacheOptions,
+    ) -> Result<Self, CacheError> {
+        if !(1..=30).contains(&options.bits) {
+            return Err(CacheError::Options("bits must be between 1 and 30"));
+        }
+        if options.keyframe_interval == 0 {
+            return Err(CacheError::Options("keyframe interval must be at least 1"));
+        }
+        if !min.is_finite() || !max.is_finite() || min.cmpge(max).any() {
+            return Err(CacheError::Options("bounds must be finite and not empty"));
+        }
+        let min = min.to_array().map(wide);
+        let max = max.to_array().map(wide);
+
+        let mut header = Vec::with_capacity(HEADER_BYTES);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&[options.bits, 0]);
+        header.extend_from_slice(&options.keyframe_interval.to_le_bytes());
+        header.extend_from_slice(&(particles as u64).to_le_bytes());
+        for x in min.iter().chain(&max) {
+            header.extend_from_slice(&x.to_le_bytes());
+        }
+        writer.write_all(&header).map_err(CacheError::Io)?;
+
+        Ok(Self {
+            writer,
+            quantizer: Quantizer::new(min, max, options.bits),
+            keyframe_interval: options.keyframe_interval,
+            particles,
+            track: Track::default(),
+            chunk: Vec::new(),
+            index: Vec::new(),
+            offset: HEADER_BYTES as u64,
+        })
+    }
+
+    pub fn frames(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn write_frame(&mut self, time: Real, positions: &[Vector]) -> Result<(), CacheError> {
+        if positions.len() != self.particles {
+            return Err(CacheError::ParticleCount {
+                expected: self.particles,
+                found: positions.len(),
+            });
+        }
+        let keyframe = self
+            .index
+            .len()
+            .is_multiple_of(self.keyframe_interval as usize);
+        self.chunk.clear();
+        self.chunk.push(if keyframe { KEYFRAME } else { DELTA });
+        let frame: Vec<_> = positions
+            .iter()
+            .map(|&p| self.quantizer.quantize(p))
+            .collect();
+        for (i, q) in frame.iter().enumerate() {
+            for (axis, &q) in q.iter().enumerate() {
+                let value = if keyframe {
+                    u64::from(q)
+                } else {
+                    zigzag(i64::from(q) - self.track.predict(i, axis))
+                };
+                write_varint(&mut self.chunk, value);
+            }
+        }
+        self.track.push(frame, keyframe);
+        self.writer.write_all(&self.chunk).map_err(CacheError::Io)?;
+        self.index.push((self.offset, wide(time)));
+        self.offset += self.chunk.len() as u64;
+        Ok(())
+    }
+
+    // write the index, the file can only be read after this
+    pub fn finish(mut self) -> Result<W, CacheError> {
+        let mut footer = Vec::with_capacity(self.index.len() * 16 + FOOTER_BYTES);
+        for (offset, time) in &self.index {
+            footer.extend_from_slice(&offset.to_le_bytes());
+            footer.extend_from_slice(&time.to_le_bytes());
+        }
+        footer.extend_from_slice(&(self.index.len() as u64).to_le_bytes());
+        footer.extend_from_slice(&self.offset.to_le_bytes());
+        footer.extend_from_slice(INDEX_MAGIC);
+        self.writer.write_all(&footer).map_err(CacheError::Io)?;
+        self.writer.flush().map_err(CacheError::Io)?;
+        Ok(self.writer)
+    }
+}
+
+// random access to the frames of a cache file, mapped into memory
+pub struct CacheReader {
+    map: Mmap,
+    quantizer: Quantizer,
+    particles: usize,
+    index: Vec<(usize, Real)>, // start of every frame and its time
+    frames_end: usize,
+}
+
+impl CacheReader {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, CacheError> {
+        let file = File::open(path).map_err(CacheError::Io)?;
+        // the mapping stays valid as long as nobody truncates the file while it is read
+        let map = unsafe { Mmap::map(&file) }.map_err(CacheError::Io)?;
+        let data = &map[..];
+
+        if data.len() < HEADER_BYTES + FOOTER_BYTES || &data[..4] != MAGIC {
+            return Err(CacheError::Corrupt("not a particle cache"));
+        }
+        if u16::from_le_bytes(read_bytes(data, 4)?) != VERSION {
+            return Err(CacheError::Corrupt("unsupported version"));
+        }
+        let bits = data[6];
+        if !(1..=30).contains(&bits) {
+            return Err(CacheError::Corrupt("invalid quantization bits"));
+        }
+        let particles = read_u64(data, 12)? as usize;
+        let mut bounds = [0.0; 6];
+        for (i, x) in bounds.iter_mut().enumerate() {
+            *x = read_f64(data, 20 + 8 * i)?;
+        }
+        let min = [bounds[0], bounds[1], bounds[2]];
+        let max = [bounds[3], bounds[4], bounds[5]];
+
+        let footer = data.len() - FOOTER_BYTES;
+        if &data[footer + 16..] != INDEX_MAGIC {
+            return Err(CacheError::Corrupt(
+                "index missing, the writer was not finished",
+            ));
+        }
+        let frames = read_u64(data, footer)? as usize;
+        let frames_end = read_u64(data, footer + 8)? as usize;
+        if frames_end < HEADER_BYTES
+            || frames.checked_mul(16) != Some(footer.saturating_sub(frames_end))
+        {
+            return Err(CacheError::Corrupt("index does not fit the file"));
+        }
+        let mut index = Vec::with_capacity(frames);
+        for i in 0..frames {
+            let entry = frames_end + 16 * i;
+            let offset = read_u64(data, entry)? as usize;
+            if offset < HEADER_BYTES || offset >= frames_end {
+                return Err(CacheError::Corrupt("frame offset out of range"));
+            }
+            index.push((offset, real(read_f64(data, entry + 8)?)));
+        }
+        // a frame holds at least the kind and one byte per particle and axis, a count from
+        // a damaged header must not size the buffers
+        let frame_bytes = particles.checked_mul(3).and_then(|n| n.checked_add(1));
+        for (i, &(offset, _)) in index.iter().enumerate() {
+            let end = index.get(i + 1).map_or(frames_end, |&(next, _)| next);
+            if frame_bytes.is_none_or(|n| end.saturating_sub(offset) < n) {
+                return Err(CacheError::Corrupt(
+                    "particle count does not fit the frames",
+                ));
+            }
+        }
+
+        Ok(Self {
+            quantizer: Quantizer::new(min, max, bits),
+            map,
+            particles,
+            index,
+            frames_end,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn particles(&self) -> usize {
+        self.particles
+    }
+
+    pub fn time(&self, frame: usize) -> Option<Real> {
+        self.index.get(frame).map(|&(_, time)| time)
+    }
+
+    pub fn bounds(&self) -> (Vector, Vector) {
+        let [x, y, z] = self.quantizer.min.map(real);
+        let [u, v, w] = self.quantizer.max.map(real);
+        (vector(x, y, z), vector(u, v, w))
+    }
+
+    // largest distance of a stored position from the written one along each axis,
+    // for positions within the bounds
+    pub fn max_error(&self) -> Vector {
+        let [x, y, z] = self.quantizer.step.map(|step| real(0.5 * step));
+        vector(x, y, z)
+    }
+
+    // decode a frame following the ones in the track
+    fn decode(&self, frame: usize, track: &mut Track) -> Result<(), CacheError> {
+        let data = &self.map[..];
+        let start = self.index[frame].0;
+        let end = self
+            .index
+            .get(frame + 1)
+            .map_or(self.frames_end, |&(offset, _)| offset);
+        let chunk = data
+            .get(start..end)
+            .ok_or(CacheError::Corrupt("frame out of order"))?;
+        let keyframe = match chunk.first() {
+            Some(&KEYFRAME) => true,
+            Some(&DELTA) if track.frames > 0 => false,
+            Some(&DELTA) => return Err(CacheError::Corrupt("delta frame without a keyframe")),
+            _ => return Err(CacheError::Corrupt("unknown frame kind")),
+        };
+        let mut decoded = Vec::with_capacity(self.particles.min(chunk.len() / 3));
+        let mut pos = 1;
+        for i in 0..self.particles {
+            let mut q = [0; 3];
+            for (axis, value) in q.iter_mut().enumerate() {
+                let x = read_varint(chunk, &mut pos)?;
+                let x = if keyframe {
+                    x as i64
+                } else {
+                    track.predict(i, axis) + unzigzag(x)
+                };
+                if !(0..=i64::from(self.quantizer.levels)).contains(&x) {
+                    return Err(CacheError::Corrupt("position out of bounds"));
+                }
+                *value = x as u32;
+            }
+            decoded.push(q);
+        }
+        if pos != chunk.len() {
+            return Err(CacheError::Corrupt("frame has trailing bytes"));
+        }
+        track.push(decoded, keyframe);
+        Ok(())
+    }
+
+    fn dequantize(&self, q: &[[u32; 3]]) -> Vec<Vector> {
+        q.iter().map(|&q| self.quantizer.dequantize(q)).collect()
+    }
+
+    pub fn frame(&self, frame: usize) -> Result<Vec<Vector>, CacheError> {
+        if frame >= self.len() {
+            return Err(CacheError::FrameOutOfRange {
+                frame,
+                frames: self.len(),
+            });
+        }
+        // back to the last keyframe, then forward through the deltas
+        let mut keyframe = frame;
+        while keyframe > 0 && self.map[self.index[keyframe].0] != KEYFRAME {
+            keyframe -= 1;
+        }
+        let mut track = Track::default();
+        for i in keyframe..=frame {
+            self.decode(i, &mut track)?;
+        }
+        Ok(self.dequantize(&track.last))
+    }
+
+    // every frame in order with its time, each decoded once
+    pub fn frames(&self) -> impl Iterator<Item = Result<(Real, Vec<Vector>), CacheError>> + '_ {
+        let mut track = Track::default();
+        (0..self.len()).map(move |frame| {
+            self.decode(frame, &mut track)?;
+            Ok((self.index[frame].1, self.dequantize(&track.last)))
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum CacheError {
+    Io(io::Error),
+    Options(&'static str),
+    ParticleCount { expected: usize, found: usize },
+    FrameOutOfRange { frame: usize, frames: usize },
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to access cache file: {err}"),
+            Self::Options(err) => write!(f, "invalid cache options: {err}"),
+            Self::ParticleCount { expected, found } => {
+                write!(f, "cache holds {expected} particles per frame, got {found}")
+            }
+            Self::FrameOutOfRange { frame, frames } => {
+                write!(
+                    f,
+                    "frame {frame} out of range, the cache has {frames} frames"
+                )
+            }
+            Self::Corrupt(err) => write!(f, "corrupt cache file: {err}"),
+        }
+    }
+}
+
+impl Error for CacheError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
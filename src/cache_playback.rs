@@ -0,0 +1,89 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use pbf_rs::cache::{CacheError, CacheReader};
+use pbf_rs::precision::Real;
+use pbf_rs::simulator::Simulator;
+
+use crate::scene::SimRunning;
+
+// particle frames of a cache written by pbf-cli, shown in place of the live simulation
+// the scene is set up as usual and only the particle positions come from the cache
+#[derive(Resource)]
+pub struct CachePlayback {
+    reader: CacheReader,
+    times: Vec<Real>,
+    time: Real,           // since the first frame
+    shown: Option<usize>, // frame the simulator holds
+}
+
+impl CachePlayback {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, CacheError> {
+        let reader = CacheReader::open(path)?;
+        let times = (0..reader.len()).filter_map(|i| reader.time(i)).collect();
+        Ok(Self {
+            reader,
+            times,
+            time: 0.0,
+            shown: None,
+        })
+    }
+
+    // the last frame at or before the playback time
+    fn frame_at(&self) -> usize {
+        let first = self.times.first().copied().unwrap_or(0.0);
+        let after = self.times.partition_point(|&t| t <= first + self.time);
+        after.saturating_sub(1)
+    }
+}
+
+// plays the frames at the speed they were simulated and starts over after the last one,
+// pausing the simulation pauses the playback
+// a cache of another scene ends the playback and the live simulation takes over
+pub fn cache_playback_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    sim_running: Res<SimRunning>,
+    playback: Option<ResMut<CachePlayback>>,
+    mut simulator: ResMut<Simulator>,
+) {
+    // a scene waiting to be set up has no particles yet
+    let Some(mut playback) = playback.filter(|_| !simulator.scene_changed) else {
+        return;
+    };
+    if playback.reader.particles() != simulator.num_sphere {
+        warn!(
+            "the cache holds {} particles but the scene has {}, stopping the playback",
+            playback.reader.particles(),
+            simulator.num_sphere
+        );
+        commands.remove_resource::<CachePlayback>();
+        return;
+    }
+    let (Some(&first), Some(&last)) = (playback.times.first(), playback.times.last()) else {
+        return;
+    };
+    if sim_running.0 {
+        playback.time += time.delta_secs() as Real;
+        if playback.time > last - first {
+            playback.time = 0.0;
+        }
+    }
+    let frame = playback.frame_at();
+    // a reset moves the particles back to the start of the scene
+    if playback.shown == Some(frame) && simulator.time == playback.times[frame] {
+        return;
+    }
+    match playback.reader.frame(frame) {
+        Ok(position) => {
+            simulator.position = position;
+            simulator.time = playback.times[frame];
+            playback.shown = Some(frame);
+        }
+        Err(err) => {
+            warn!("failed to read frame {frame} of the cache: {err}");
+            commands.remove_resource::<CachePlayback>();
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+use std::fs;
+
+use bevy::{
+    input::mouse::{AccumulatedMouseMotion, MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use pbf_rs::precision::{to_f32, to_vec3};
+use pbf_rs::simulator::Simulator;
+
+use crate::library::SceneLibrary;
+
+const POSES_FILE: &str = "camera_poses.ron";
+const KEY_ROTATION_SPEED: f32 = 1.0; // radians per second
+const DRAG_ROTATION_SPEED: f32 = 0.005; // radians per pixel
+const PAN_SPEED: f32 = 0.0012; // distances per pixel
+const ZOOM_STEP: f32 = 1.15; // distance factor per wheel notch
+const SMOOTHING: f32 = 15.0; // 1/s, how fast the camera follows its target pose
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+const MIN_DISTANCE: f32 = 0.2;
+const MAX_DISTANCE: f32 = 50.0;
+
+// where the camera looks from, yaw turns about the vertical and pitch raises it above the focus
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Pose {
+    pub focus: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+}
+
+impl Default for Pose {
+    fn default() -> Self {
+        Self {
+            focus: [0.0; 3],
+            yaw: 0.0,
+            pitch: 0.0,
+            distance: 3.0,
+        }
+    }
+}
+
+impl Pose {
+    fn transform(&self) -> Transform {
+        let focus = Vec3::from(self.focus);
+        let rotation = Quat::from_rotation_y(self.yaw) * Quat::from_rotation_x(-self.pitch);
+        Transform::from_translation(focus + rotation * Vec3::new(0.0, 0.0, self.distance))
+            .looking_at(focus, Vec3::Y)
+    }
+
+    // move a fraction of the way to the other pose
+    fn approach(&mut self, target: &Pose, t: f32) {
+        let focus = Vec3::from(self.focus).lerp(Vec3::from(target.focus), t);
+        self.focus = focus.into();
+        self.yaw += (target.yaw - self.yaw) * t;
+        self.pitch += (target.pitch - self.pitch) * t;
+        // zoom evenly on a log scale
+        self.distance *= (target.distance / self.distance).powf(t);
+    }
+}
+
+// the same direction as angle, the fewest turns away from current
+fn nearest_angle(current: f32, angle: f32) -> f32 {
+    angle + ((current - angle) / TAU).round() * TAU
+}
+
+// an orbit camera easing towards a target pose, with the poses saved for each scene
+#[derive(Resource, Default)]
+pub struct OrbitCamera {
+    pose: Pose,
+    target: Pose,
+    saved: HashMap<String, Pose>, // by scene name
+    scene: String,                // the scene shown
+    dragging: Option<MouseButton>,
+}
+
+impl OrbitCamera {
+    // the poses saved in earlier sessions, none when the file is missing or unreadable
+    pub fn load() -> Self {
+        let saved = match fs::read_to_string(POSES_FILE) {
+            Ok(text) => ron::from_str(&text).unwrap_or_else(|err| {
+                warn!("ignoring {POSES_FILE}: {err}");
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Self { saved, ..default() }
+    }
+
+    fn set_target(&mut self, target: Pose) {
+        self.target = Pose {
+            yaw: nearest_angle(self.pose.yaw, target.yaw),
+            ..target
+        };
+    }
+
+    fn save(&mut self) {
+        self.saved.insert(self.scene.clone(), self.target);
+        let result = ron::ser::to_string_pretty(&self.saved, Default::default())
+            .map_err(|err| err.to_string())
+            .and_then(|text| fs::write(POSES_FILE, text).map_err(|err| err.to_string()));
+        match result {
+            Ok(()) => info!("camera pose of {:?} saved to {POSES_FILE}", self.scene),
+            Err(err) => warn!("failed to write {POSES_FILE}: {err}"),
+        }
+    }
+
+    // the saved pose of the scene shown, the default without one
+    fn restore(&mut self) {
+        let pose = self.saved.get(&self.scene).copied().unwrap_or_default();
+        self.set_target(pose);
+    }
+}
+
+// a pose looking at the particles from the current direction, close enough to see all of them
+fn frame_fluid(simulator: &Simulator, pose: &Pose, projection: Option<&Projection>) -> Pose {
+    let mut min = Vec3::splat(f32::INFINITY);
+    let mut max = Vec3::splat(f32::NEG_INFINITY);
+    for &p in &simulator.position {
+        let p = to_vec3(simulator.to_world(p));
+        min = min.min(p);
+        max = max.max(p);
+    }
+    if !min.is_finite() || !max.is_finite() {
+        return *pose;
+    }
+    let radius = 0.5 * (max - min).length() + to_f32(simulator.radius);
+    // the narrower of the vertical and horizontal field of view
+    let half_fov = match projection {
+        Some(Projection::Perspective(p)) => ((p.fov / 2.0).tan() * p.aspect_ratio.min(1.0)).atan(),
+        _ => PI / 8.0,
+    };
+    Pose {
+        focus: (0.5 * (min + max)).into(),
+        distance: (radius / half_fov.sin()).clamp(MIN_DISTANCE, MAX_DISTANCE),
+        ..*pose
+    }
+}
+
+// left drag or the arrow keys orbit, right drag pans and the mouse wheel zooms
+// F frames the fluid, 1, 2 and 3 look from the front, the side and the top,
+// C saves the pose for the current scene and V goes back to it
+#[allow(clippy::too_many_arguments)]
+pub fn camera_control_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mouse_motion: Res<AccumulatedMouseMotion>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    ui_query: Query<&Interaction>,
+    time: Res<Time>,
+    simulator: Res<Simulator>,
+    library: Res<SceneLibrary>,
+    mut orbit: ResMut<OrbitCamera>,
+    mut camera_query: Query<(&mut Transform, Option<&Projection>), With<Camera3d>>,
+) {
+    let Ok((mut transform, projection)) = camera_query.single_mut() else {
+        return;
+    };
+
+    // another scene brings its own saved pose
+    if library.current().name != orbit.scene {
+        orbit.scene = library.current().name.clone();
+        orbit.restore();
+    }
+
+    // drags start off the ui and without the modifiers that tilt the tank or grab the fluid
+    let modifier = keys.any_pressed([
+        KeyCode::ShiftLeft,
+        KeyCode::ShiftRight,
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+    ]);
+    let over_ui = ui_query.iter().any(|i| *i != Interaction::None);
+    for button in [MouseButton::Left, MouseButton::Right] {
+        if mouse.just_pressed(button) && !modifier && !over_ui {
+            orbit.dragging = Some(button);
+        }
+    }
+    if orbit.dragging.is_some_and(|button| !mouse.pressed(button)) {
+        orbit.dragging = None;
+    }
+
+    let delta = mouse_motion.delta;
+    let mut target = orbit.target;
+    match orbit.dragging {
+        Some(MouseButton::Left) => {
+            target.yaw -= delta.x * DRAG_ROTATION_SPEED;
+            target.pitch += delta.y * DRAG_ROTATION_SPEED;
+        }
+        Some(MouseButton::Right) => {
+            // the focus follows the cursor in the view plane
+            let step = PAN_SPEED * target.distance;
+            let pan = transform.right() * -delta.x * step + transform.up() * delta.y * step;
+            target.focus = (Vec3::from(target.focus) + pan).into();
+        }
+        _ => {}
+    }
+
+    let step = KEY_ROTATION_SPEED * time.delta_secs();
+    if keys.pressed(KeyCode::ArrowLeft) {
+        target.yaw += step;
+    }
+    if keys.pressed(KeyCode::ArrowRight) {
+        target.yaw -= step;
+    }
+    if keys.pressed(KeyCode::ArrowUp) {
+        target.pitch -= step;
+    }
+    if keys.pressed(KeyCode::ArrowDown) {
+        target.pitch += step;
+    }
+
+    for ev in mouse_wheel_events.read() {
+        let notches = match ev.unit {
+            MouseScrollUnit::Line => ev.y,
+            MouseScrollUnit::Pixel => ev.y / 40.0,
+        };
+        target.distance /= ZOOM_STEP.powf(notches);
+    }
+    target.pitch = target.pitch.clamp(-MAX_PITCH, MAX_PITCH);
+    target.distance = target.distance.clamp(MIN_DISTANCE, MAX_DISTANCE);
+    orbit.target = target;
+
+    if keys.just_pressed(KeyCode::KeyF) {
+        let pose = frame_fluid(&simulator, &orbit.target, projection);
+        orbit.set_target(pose);
+    }
+    for (key, yaw, pitch) in [
+        (KeyCode::Digit1, 0.0, 0.0),
+        (KeyCode::Digit2, FRAC_PI_2, 0.0),
+        (KeyCode::Digit3, 0.0, MAX_PITCH),
+    ] {
+        if keys.just_pressed(key) {
+            let pose = Pose {
+                yaw,
+                pitch,
+                ..orbit.target
+            };
+            orbit.set_target(pose);
+        }
+    }
+    if keys.just_pressed(KeyCode::KeyC) {
+        orbit.save();
+    }
+    if keys.just_pressed(KeyCode::KeyV) {
+        orbit.restore();
+    }
+
+    let t = 1.0 - (-SMOOTHING * time.delta_secs()).exp();
+    let target = orbit.target;
+    orbit.pose.approach(&target, t);
+    *transform = orbit.pose.transform();
+}
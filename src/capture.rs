@@ -0,0 +1,171 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy::render::view::screenshot::{Screenshot, ScreenshotCaptured};
+use bevy::tasks::IoTaskPool;
+
+use pbf_rs::precision::Real;
+use pbf_rs::simulator::Simulator;
+
+const CAPTURE_DIR: &str = "captures";
+
+// rendered frames written to numbered PNGs, one per 1/fps of simulated time, so a sequence
+// plays back at the speed of the simulation whatever frame rate the viewer ran at
+#[derive(Resource)]
+pub struct Capture {
+    pub fps: Real, // frames per simulated second
+    recording: Option<Recording>,
+}
+
+struct Recording {
+    dir: PathBuf,
+    frame: usize,
+    next: Real, // simulated time of the next frame
+    last: Real, // simulated time of the last frame
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Self {
+            fps: 30.0,
+            recording: None,
+        }
+    }
+}
+
+impl Capture {
+    // the simulated time to step to before the next frame is rendered, None when not recording
+    pub fn target_time(&mut self, time: Real) -> Option<Real> {
+        let recording = self.recording.as_mut()?;
+        // the simulation was reset, the sequence goes on from the new state
+        if time < recording.last {
+            recording.next = time;
+            recording.last = time;
+        }
+        Some(recording.next)
+    }
+}
+
+#[derive(Component)]
+pub struct CaptureText;
+
+pub fn setup_capture_text(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.spawn((
+        CaptureText,
+        Text::new(""),
+        TextFont {
+            font: assets.load("fonts/FiraSans-Bold.ttf"),
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(Color::srgb(1.0, 0.2, 0.2)),
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(20.0),
+            bottom: Val::Px(20.0),
+            ..default()
+        },
+    ));
+}
+
+// the first path the numbering has not used yet, earlier captures are kept
+fn unused_path(name: impl Fn(usize) -> String) -> PathBuf {
+    (0..)
+        .map(|i| Path::new(CAPTURE_DIR).join(name(i)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+// encode off the main thread, a recording would stall the viewer otherwise
+fn save_png(path: PathBuf) -> impl FnMut(Trigger<ScreenshotCaptured>) {
+    move |trigger| {
+        let image = trigger.event().0.clone();
+        let path = path.clone();
+        IoTaskPool::get()
+            .spawn(async move {
+                let result = match image.try_into_dynamic() {
+                    // the alpha channel holds brightness with hdr, drop it
+                    Ok(image) => image.to_rgb8().save(&path).map_err(|err| err.to_string()),
+                    Err(err) => Err(err.to_string()),
+                };
+                if let Err(err) = result {
+                    warn!("failed to write {}: {err}", path.display());
+                }
+            })
+            .detach();
+    }
+}
+
+// P saves a screenshot to captures/screenshot_###.png
+// R starts and stops recording to captures/recording_###/frame_#####.png
+pub fn capture_system(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    simulator: Res<Simulator>,
+    mut capture: ResMut<Capture>,
+    mut text_query: Query<&mut Text, With<CaptureText>>,
+) {
+    if keys.just_pressed(KeyCode::KeyP) {
+        match fs::create_dir_all(CAPTURE_DIR) {
+            Ok(()) => {
+                let path = unused_path(|i| format!("screenshot_{i:03}.png"));
+                info!("screenshot saved to {}", path.display());
+                commands
+                    .spawn(Screenshot::primary_window())
+                    .observe(save_png(path));
+            }
+            Err(err) => warn!("failed to create {CAPTURE_DIR}: {err}"),
+        }
+    }
+    if keys.just_pressed(KeyCode::KeyR) {
+        if let Some(recording) = capture.recording.take() {
+            info!(
+                "stopped recording after {} frames in {}",
+                recording.frame,
+                recording.dir.display()
+            );
+        } else {
+            let dir = unused_path(|i| format!("recording_{i:03}"));
+            match fs::create_dir_all(&dir) {
+                Ok(()) => {
+                    info!("recording at {} fps to {}", capture.fps, dir.display());
+                    capture.recording = Some(Recording {
+                        dir,
+                        frame: 0,
+                        next: simulator.time,
+                        last: simulator.time,
+                    });
+                }
+                Err(err) => warn!("failed to create {}: {err}", dir.display()),
+            }
+        }
+    }
+
+    let interval = 1.0 / capture.fps;
+    let dt = simulator.params().time_step();
+    if let Some(recording) = &mut capture.recording {
+        // nothing new while the simulation is stopped
+        if simulator.time + 0.5 * dt >= recording.next {
+            let path = recording
+                .dir
+                .join(format!("frame_{:05}.png", recording.frame));
+            commands
+                .spawn(Screenshot::primary_window())
+                .observe(save_png(path));
+            recording.frame += 1;
+            recording.last = simulator.time;
+            recording.next += interval;
+        }
+    }
+
+    let status = match &capture.recording {
+        Some(recording) => format!("REC {} frames", recording.frame),
+        None => String::new(),
+    };
+    for mut text in &mut text_query {
+        if **text != status {
+            **text = status.clone();
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::diffuse::DiffuseSystem;
+use crate::params::{SimParams, SimParamsError};
+use crate::precision::{Real, Rotation, Vector};
+use crate::scene_desc::SceneDesc;
+
+// motion of a rigid body, its shape and density come from the scene
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BodyState {
+    pub position: Vector,
+    pub rotation: Rotation,
+    pub velocity: Vector,
+    pub angular_velocity: Vector,
+}
+
+// everything needed to continue a simulation where it was saved
+// restoring sets the scene up again and then moves everything to the saved state
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub scene: SceneDesc,
+    pub state: SimState,
+}
+
+// what changes while a scene runs, a scene that is already set up can be moved to it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SimState {
+    pub params: SimParams,
+    pub time: Real,
+    pub tank_rotation: Rotation,
+    pub position: Vec<Vector>,
+    pub velocity: Vec<Vector>,
+    pub rigid_bodies: Vec<BodyState>,
+    pub solids: Vec<Rotation>, // last rotation of every shape matching body, warm starts the next
+    pub diffuse: Option<DiffuseSystem>,
+}
+
+impl Checkpoint {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CheckpointError> {
+        let text = fs::read_to_string(path).map_err(CheckpointError::Io)?;
+        ron::from_str(&text).map_err(CheckpointError::Parse)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CheckpointError> {
+        let text = ron::to_string(self).map_err(CheckpointError::Write)?;
+        fs::write(path, text).map_err(CheckpointError::Io)
+    }
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Write(ron::Error),
+    Params(SimParamsError),
+    // the scene sets up a different number of particles or bodies than were saved
+    Mismatch {
+        what: &'static str,
+        saved: usize,
+        scene: usize,
+    },
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to access checkpoint file: {err}"),
+            Self::Parse(err) => write!(f, "failed to parse checkpoint: {err}"),
+            Self::Write(err) => write!(f, "failed to serialize checkpoint: {err}"),
+            Self::Params(err) => write!(f, "invalid parameters in checkpoint: {err}"),
+            Self::Mismatch { what, saved, scene } => write!(
+                f,
+                "checkpoint holds {saved} {what} but its scene sets up {scene}"
+            ),
+        }
+    }
+}
+
+impl Error for CheckpointError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Parse(err) => Some(err),
+            Self::Write(err) => Some(err),
+            Self::Params(err) => Some(err),
+            Self::Mismatch { .. } => None,
+        }
+    }
+}
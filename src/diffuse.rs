@@ -0,0 +1,241 @@
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::precision::{Real, Vector};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiffuseKind {
+    Spray,  // in the air, flies ballistically
+    Foam,   // on the surface, carried along by the fluid
+    Bubble, // under water, rises and is dragged along by the fluid
+}
+
+impl DiffuseKind {
+    fn id(self) -> u8 {
+        match self {
+            DiffuseKind::Spray => 0,
+            DiffuseKind::Foam => 1,
+            DiffuseKind::Bubble => 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DiffuseParticle {
+    pub position: Vector, // in the tank frame
+    pub velocity: Vector,
+    pub kind: DiffuseKind,
+    pub age: Real, // seconds since the particle was emitted
+}
+
+// secondary particles emitted by the fluid (Ihmsen et al. 2012)
+// every potential is mapped to [0, 1] between its min and max
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiffuseParams {
+    pub trapped_air: (Real, Real), // relative velocity of colliding particles, m/s
+    pub wave_crest: (Real, Real),  // surface curvature of moving crests
+    pub kinetic_energy: (Real, Real), // per unit mass, J/kg
+    pub trapped_air_rate: Real,    // particles emitted per second at full potentials
+    pub wave_crest_rate: Real,
+    pub spray_density: Real, // below this fraction of the rest density a particle is spray
+    pub bubble_density: Real, // above this fraction of the rest density a particle is a bubble
+    pub spray_lifetime: Real, // seconds
+    pub foam_lifetime: Real,
+    pub bubble_lifetime: Real,
+    pub buoyancy: Real, // upward acceleration of bubbles in units of gravity
+    pub drag: Real,     // rate at which bubbles take over the fluid velocity, 1/s
+    pub max_particles: usize,
+    pub seed: u64,
+}
+
+impl Default for DiffuseParams {
+    fn default() -> Self {
+        Self {
+            trapped_air: (2.0, 10.0),
+            wave_crest: (1.0, 4.0),
+            kinetic_energy: (0.5, 4.0),
+            trapped_air_rate: 40.0,
+            wave_crest_rate: 40.0,
+            spray_density: 0.3,
+            bubble_density: 0.8,
+            spray_lifetime: 1.5,
+            foam_lifetime: 3.0,
+            bubble_lifetime: 2.0,
+            buoyancy: 2.0,
+            drag: 10.0,
+            max_particles: 10000,
+            seed: 1,
+        }
+    }
+}
+
+impl DiffuseParams {
+    pub fn lifetime(&self, kind: DiffuseKind) -> Real {
+        match kind {
+            DiffuseKind::Spray => self.spray_lifetime,
+            DiffuseKind::Foam => self.foam_lifetime,
+            DiffuseKind::Bubble => self.bubble_lifetime,
+        }
+    }
+
+    // kind of a particle from the fluid density around it, relative to the rest density
+    pub fn classify(&self, density: Real) -> DiffuseKind {
+        if density < self.spray_density {
+            DiffuseKind::Spray
+        } else if density > self.bubble_density {
+            DiffuseKind::Bubble
+        } else {
+            DiffuseKind::Foam
+        }
+    }
+
+    // name of the first potential range that is empty or not finite, emission divides by
+    // the width of every range
+    pub fn invalid_range(&self) -> Option<&'static str> {
+        [
+            ("trapped_air", self.trapped_air),
+            ("wave_crest", self.wave_crest),
+            ("kinetic_energy", self.kinetic_energy),
+        ]
+        .into_iter()
+        .find(|(_, (min, max))| !(min < max && min.is_finite() && max.is_finite()))
+        .map(|(name, _)| name)
+    }
+
+    // expected number of particles a fluid particle emits during dt
+    pub fn emission(&self, trapped_air: Real, wave_crest: Real, kinetic_energy: Real) -> Real {
+        let clamp = |value: Real, (min, max): (Real, Real)| {
+            ((value.min(max) - value.min(min)) / (max - min)).max(0.0)
+        };
+        clamp(kinetic_energy, self.kinetic_energy)
+            * (self.trapped_air_rate * clamp(trapped_air, self.trapped_air)
+                + self.wave_crest_rate * clamp(wave_crest, self.wave_crest))
+    }
+}
+
+// xorshift64*, keeps the emission reproducible without pulling in a random crate
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    // uniform in [0, 1)
+    fn next(&mut self) -> Real {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let bits = self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 40;
+        (bits as f64 / (1u64 << 24) as f64) as Real
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DiffuseSystem {
+    pub params: DiffuseParams,
+    pub particles: Vec<DiffuseParticle>,
+    rng: Rng,
+}
+
+impl DiffuseSystem {
+    pub fn new(params: DiffuseParams) -> Self {
+        Self {
+            rng: Rng::new(params.seed),
+            params,
+            particles: Vec::new(),
+        }
+    }
+
+    // emit particles in the cylinder swept by a fluid particle of the given radius during dt
+    // a fractional expected count is emitted with the matching probability
+    pub fn emit(
+        &mut self,
+        position: Vector,
+        velocity: Vector,
+        expected: Real,
+        radius: Real,
+        dt: Real,
+    ) {
+        let mut count = expected.floor() as usize;
+        if self.rng.next() < expected.fract() {
+            count += 1;
+        }
+        let axis = velocity.normalize_or_zero();
+        let e1 = axis.any_orthonormal_vector();
+        let e2 = axis.cross(e1);
+        let length = velocity.length() * dt;
+        for _ in 0..count {
+            if self.particles.len() >= self.params.max_particles {
+                return;
+            }
+            let r = radius * self.rng.next().sqrt();
+            let theta = self.rng.next() * 2.0 * std::f64::consts::PI as Real;
+            let (s, c) = theta.sin_cos();
+            let offset = r * c * e1 + r * s * e2;
+            self.particles.push(DiffuseParticle {
+                position: position + offset + self.rng.next() * length * axis,
+                velocity: velocity + offset,
+                kind: DiffuseKind::Foam,
+                age: 0.0,
+            });
+        }
+    }
+
+    // write the particles as an ascii PLY point cloud
+    pub fn write_ply(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "ply")?;
+        writeln!(writer, "format ascii 1.0")?;
+        writeln!(writer, "comment kind 0 spray, 1 foam, 2 bubble")?;
+        writeln!(writer, "element vertex {}", self.particles.len())?;
+        for name in ["x", "y", "z", "vx", "vy", "vz"] {
+            writeln!(writer, "property float {name}")?;
+        }
+        writeln!(writer, "property uchar kind")?;
+        writeln!(writer, "property float age")?;
+        writeln!(writer, "end_header")?;
+        for p in &self.particles {
+            let (x, v) = (p.position, p.velocity);
+            writeln!(
+                writer,
+                "{} {} {} {} {} {} {} {}",
+                x.x,
+                x.y,
+                x.z,
+                v.x,
+                v.y,
+                v.z,
+                p.kind.id(),
+                p.age
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// move a particle for one step given the fluid density (relative to the rest density)
+// and the kernel weighted fluid velocity around it
+pub fn advect(
+    particle: &mut DiffuseParticle,
+    params: &DiffuseParams,
+    density: Real,
+    fluid_velocity: Vector,
+    gravity: Vector,
+    dt: Real,
+) {
+    particle.kind = params.classify(density);
+    match particle.kind {
+        DiffuseKind::Spray => particle.velocity += gravity * dt,
+        DiffuseKind::Foam => particle.velocity = fluid_velocity,
+        DiffuseKind::Bubble => {
+            let drag = (params.drag * dt).min(1.0);
+            particle.velocity +=
+                -params.buoyancy * gravity * dt + drag * (fluid_velocity - particle.velocity);
+        }
+    }
+    particle.position += particle.velocity * dt;
+    particle.age += dt;
+}
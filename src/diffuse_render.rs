@@ -0,0 +1,145 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+
+use bevy::prelude::*;
+
+use pbf_rs::diffuse::DiffuseKind;
+use pbf_rs::precision::{Real, to_f32, to_quat, to_vec3};
+use pbf_rs::simulator::Simulator;
+
+const EXPORT_DIR: &str = "export";
+
+#[derive(Component)]
+pub struct DiffuseMesh(usize);
+
+// one mesh shared by all diffuse particles and a material for each kind
+#[derive(Resource)]
+pub struct DiffuseAssets {
+    mesh: Handle<Mesh>,
+    spray: Handle<StandardMaterial>,
+    foam: Handle<StandardMaterial>,
+    bubble: Handle<StandardMaterial>,
+}
+
+impl DiffuseAssets {
+    // material and size relative to the fluid particle radius
+    fn style(&self, kind: DiffuseKind) -> (Handle<StandardMaterial>, Vec3) {
+        match kind {
+            DiffuseKind::Spray => (self.spray.clone(), Vec3::splat(0.3)),
+            // flat flakes lying on the surface
+            DiffuseKind::Foam => (self.foam.clone(), Vec3::new(0.6, 0.2, 0.6)),
+            DiffuseKind::Bubble => (self.bubble.clone(), Vec3::splat(0.45)),
+        }
+    }
+}
+
+pub fn setup_diffuse_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(DiffuseAssets {
+        mesh: meshes.add(Sphere::new(1.0).mesh().ico(1).unwrap()),
+        spray: materials.add(StandardMaterial {
+            base_color: Color::WHITE,
+            unlit: true,
+            ..default()
+        }),
+        foam: materials.add(StandardMaterial {
+            base_color: Color::srgb(0.9, 0.95, 1.0),
+            perceptual_roughness: 1.0,
+            ..default()
+        }),
+        bubble: materials.add(StandardMaterial {
+            base_color: Color::srgba(0.7, 0.9, 1.0, 0.4),
+            alpha_mode: AlphaMode::Blend,
+            ..default()
+        }),
+    });
+}
+
+// keep one entity per diffuse particle and move it to the particle
+pub fn diffuse_render_system(
+    mut commands: Commands,
+    simulator: Res<Simulator>,
+    assets: Res<DiffuseAssets>,
+    mut query: Query<(
+        Entity,
+        &DiffuseMesh,
+        &mut Transform,
+        &mut MeshMaterial3d<StandardMaterial>,
+    )>,
+) {
+    let particles = simulator.diffuse().map_or(&[][..], |d| &d.particles);
+    let radius = to_f32(simulator.radius);
+    let rotation = to_quat(simulator.tank_rotation());
+
+    let mut existing = 0;
+    for (entity, mesh, mut transform, mut material) in &mut query {
+        let Some(p) = particles.get(mesh.0) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        let (handle, scale) = assets.style(p.kind);
+        transform.translation = to_vec3(simulator.to_world(p.position));
+        transform.rotation = rotation;
+        transform.scale = scale * radius;
+        material.0 = handle;
+        existing += 1;
+    }
+
+    for (i, p) in particles.iter().enumerate().skip(existing) {
+        let (handle, scale) = assets.style(p.kind);
+        commands.spawn((
+            Mesh3d(assets.mesh.clone()),
+            MeshMaterial3d(handle),
+            Transform {
+                translation: to_vec3(simulator.to_world(p.position)),
+                rotation,
+                scale: scale * radius,
+            },
+            DiffuseMesh(i),
+        ));
+    }
+}
+
+// E starts and stops writing the diffuse particles of every step to export/diffuse_#####.ply
+pub fn diffuse_export_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    simulator: Res<Simulator>,
+    mut exporting: Local<bool>,
+    mut frame: Local<usize>,
+    mut last_time: Local<Option<Real>>,
+) {
+    if keys.just_pressed(KeyCode::KeyE) {
+        *exporting = !*exporting;
+        if *exporting {
+            if let Err(err) = fs::create_dir_all(EXPORT_DIR) {
+                warn!("failed to create {EXPORT_DIR}: {err}");
+                *exporting = false;
+                return;
+            }
+            *frame = 0;
+            info!("exporting diffuse particles to {EXPORT_DIR}");
+        } else {
+            info!("stopped exporting after {} frames", *frame);
+        }
+    }
+    // nothing new to write while the simulation is stopped
+    if !*exporting || *last_time == Some(simulator.time) {
+        return;
+    }
+    *last_time = Some(simulator.time);
+    let Some(diffuse) = simulator.diffuse() else {
+        return;
+    };
+
+    let path = format!("{EXPORT_DIR}/diffuse_{:05}.ply", *frame);
+    let result = File::create(&path).and_then(|file| diffuse.write_ply(BufWriter::new(file)));
+    if let Err(err) = result {
+        warn!("failed to write {path}: {err}");
+        *exporting = false;
+        return;
+    }
+    *frame += 1;
+}
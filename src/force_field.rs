@@ -0,0 +1,272 @@
+use serde::{Deserialize, Serialize};
+
+use crate::precision::{Real, Vector, vector};
+
+// an external acceleration acting on the particles, placed in the tank frame
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ForceField {
+    // pulls particles within radius towards center, a negative strength repels them
+    Attractor {
+        center: Vector,
+        strength: Real,
+        radius: Real,
+    },
+    // swirls particles within radius around the line through center along axis
+    Vortex {
+        center: Vector,
+        axis: Vector,
+        strength: Real,
+        radius: Real,
+    },
+    // drags particles towards the wind velocity
+    Wind {
+        velocity: Vector,
+        drag: Real,
+    },
+    // divergence free curl noise, scale is the size of the eddies
+    Turbulence {
+        strength: Real,
+        scale: Real,
+        speed: Real, // how fast the noise field evolves
+        seed: u32,
+    },
+    // slows down particles inside the box between min and max
+    Drag {
+        min: Vector,
+        max: Vector,
+        coefficient: Real,
+    },
+}
+
+impl ForceField {
+    pub fn acceleration(&self, pos: Vector, vel: Vector, time: Real) -> Vector {
+        match self {
+            ForceField::Attractor {
+                center,
+                strength,
+                radius,
+            } => {
+                let d = *center - pos;
+                let dist = d.length();
+                if dist >= *radius || dist < 1e-12 {
+                    return Vector::ZERO;
+                }
+                strength * (1.0 - dist / radius) * d / dist
+            }
+            ForceField::Vortex {
+                center,
+                axis,
+                strength,
+                radius,
+            } => {
+                let axis = axis.normalize_or_zero();
+                let r = pos - *center;
+                let r_perp = r - r.dot(axis) * axis;
+                let dist = r_perp.length();
+                if dist >= *radius || dist < 1e-12 {
+                    return Vector::ZERO;
+                }
+                strength * (1.0 - dist / radius) * axis.cross(r_perp) / dist
+            }
+            ForceField::Wind { velocity, drag } => *drag * (*velocity - vel),
+            ForceField::Turbulence {
+                strength,
+                scale,
+                speed,
+                seed,
+            } => {
+                let p = pos / *scale + Vector::splat(time * speed);
+                *strength * curl_noise(p, *seed)
+            }
+            ForceField::Drag {
+                min,
+                max,
+                coefficient,
+            } => {
+                if pos.cmpge(*min).all() && pos.cmple(*max).all() {
+                    -*coefficient * vel
+                } else {
+                    Vector::ZERO
+                }
+            }
+        }
+    }
+}
+
+fn hash(x: i32, y: i32, z: i32, seed: u32) -> u32 {
+    let mut h = seed
+        ^ (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ (z as u32).wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^ (h >> 16)
+}
+
+// gradient of the lattice point, one of the 12 cube edge directions
+fn gradient(x: i32, y: i32, z: i32, seed: u32) -> Vector {
+    const GRADIENTS: [[Real; 3]; 12] = [
+        [1.0, 1.0, 0.0],
+        [-1.0, 1.0, 0.0],
+        [1.0, -1.0, 0.0],
+        [-1.0, -1.0, 0.0],
+        [1.0, 0.0, 1.0],
+        [-1.0, 0.0, 1.0],
+        [1.0, 0.0, -1.0],
+        [-1.0, 0.0, -1.0],
+        [0.0, 1.0, 1.0],
+        [0.0, -1.0, 1.0],
+        [0.0, 1.0, -1.0],
+        [0.0, -1.0, -1.0],
+    ];
+    let [gx, gy, gz] = GRADIENTS[(hash(x, y, z, seed) % 12) as usize];
+    vector(gx, gy, gz)
+}
+
+fn fade(t: Real) -> Real {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+// 3d gradient noise in roughly [-1, 1]
+fn noise(p: Vector, seed: u32) -> Real {
+    let cell = p.floor();
+    let f = p - cell;
+    let (x, y, z) = (cell.x as i32, cell.y as i32, cell.z as i32);
+    let u = vector(fade(f.x), fade(f.y), fade(f.z));
+
+    let corner = |i: i32, j: i32, k: i32| {
+        let offset = vector(i as Real, j as Real, k as Real);
+        gradient(x + i, y + j, z + k, seed).dot(f - offset)
+    };
+    let lerp = |a: Real, b: Real, t: Real| a + (b - a) * t;
+
+    let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), u.x);
+    let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), u.x);
+    let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), u.x);
+    let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), u.x);
+    lerp(lerp(x00, x10, u.y), lerp(x01, x11, u.y), u.z)
+}
+
+// curl of a vector potential made of three decorrelated noise fields
+pub fn curl_noise(p: Vector, seed: u32) -> Vector {
+    const EPS: Real = 1e-2;
+    let potential = |p: Vector| {
+        vector(
+            noise(p, seed),
+            noise(p, seed.wrapping_add(0x9e37_79b9)),
+            noise(p, seed.wrapping_add(0x3c6e_f372)),
+        )
+    };
+    let dx = (potential(p + Vector::X * EPS) - potential(p - Vector::X * EPS)) / (2.0 * EPS);
+    let dy = (potential(p + Vector::Y * EPS) - potential(p - Vector::Y * EPS)) / (2.0 * EPS);
+    let dz = (potential(p + Vector::Z * EPS) - potential(p - Vector::Z * EPS)) / (2.0 * EPS);
+    vector(dy.z - dz.y, dz.x - dx.z, dx.y - dy.x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attractor_falls_off_linearly_and_repels_with_negative_strength() {
+        let center = vector(0.1, 0.2, 0.0);
+        let attractor = |strength| ForceField::Attractor {
+            center,
+            strength,
+            radius: 0.4,
+        };
+        let at = |field: &ForceField, dist: Real| {
+            field.acceleration(center + vector(dist, 0.0, 0.0), Vector::ZERO, 0.0)
+        };
+        let pull = attractor(8.0);
+        let near = at(&pull, 0.1);
+        let far = at(&pull, 0.3);
+        // towards the center, 3/4 and 1/4 of the strength
+        assert!(near.x < 0.0 && far.x < 0.0);
+        assert!((near.length() - 6.0).abs() < 1e-4, "{near}");
+        assert!((far.length() - 2.0).abs() < 1e-4, "{far}");
+        assert_eq!(at(&pull, 0.5), Vector::ZERO);
+        assert_eq!(at(&attractor(-8.0), 0.1), -near);
+    }
+
+    #[test]
+    fn vortex_swirls_around_its_axis() {
+        let (center, axis) = (vector(0.0, -0.1, 0.2), vector(1.0, 2.0, 0.5));
+        let vortex = ForceField::Vortex {
+            center,
+            axis,
+            strength: 3.0,
+            radius: 0.5,
+        };
+        let axis = axis.normalize();
+        for pos in [
+            vector(0.2, 0.0, 0.1),
+            vector(-0.1, 0.1, 0.4),
+            vector(0.0, 0.2, 0.0),
+        ] {
+            let a = vortex.acceleration(pos, Vector::ZERO, 0.0);
+            let r = pos - center;
+            let r_perp = r - r.dot(axis) * axis;
+            assert!(a.length() > 0.1, "{a} at {pos}");
+            assert!(a.dot(axis).abs() < 1e-5, "{a} at {pos}");
+            assert!(a.dot(r_perp).abs() < 1e-5, "{a} at {pos}");
+            // counterclockwise around the axis
+            assert!(r_perp.cross(a).dot(axis) > 0.0);
+        }
+    }
+
+    #[test]
+    fn drag_and_wind_oppose_the_relative_velocity() {
+        let drag = ForceField::Drag {
+            min: Vector::splat(-0.1),
+            max: Vector::splat(0.1),
+            coefficient: 2.0,
+        };
+        let wind = vector(0.5, 0.0, -0.2);
+        let wind_field = ForceField::Wind {
+            velocity: wind,
+            drag: 1.5,
+        };
+        for vel in [vector(1.0, 0.0, 0.0), vector(-0.3, 0.7, 0.2)] {
+            let a = drag.acceleration(Vector::ZERO, vel, 0.0);
+            assert!(
+                a.dot(vel) < 0.0 && a.cross(vel).length() < 1e-6,
+                "{a} {vel}"
+            );
+            let outside = drag.acceleration(Vector::splat(0.2), vel, 0.0);
+            assert_eq!(outside, Vector::ZERO);
+
+            let relative = vel - wind;
+            let a = wind_field.acceleration(Vector::ZERO, vel, 0.0);
+            assert!(a.dot(relative) < 0.0 && a.cross(relative).length() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn turbulence_is_divergence_free() {
+        let turbulence = ForceField::Turbulence {
+            strength: 2.0,
+            scale: 0.3,
+            speed: 0.5,
+            seed: 7,
+        };
+        let field = |p: Vector| turbulence.acceleration(p, Vector::ZERO, 1.3);
+        let e = 2e-3;
+        for i in 0..20 {
+            let t = i as Real;
+            let p = vector((0.37 * t).sin(), (0.51 * t).cos(), 0.013 * t * t - 0.4);
+            // the divergence cancels out of terms that are each far from zero
+            let terms = [Vector::X, Vector::Y, Vector::Z]
+                .map(|axis| (field(p + e * axis) - field(p - e * axis)).dot(axis) / (2.0 * e));
+            let divergence: Real = terms.iter().sum();
+            let size: Real = terms.iter().map(|d| d.abs()).sum();
+            assert!(size > 1.0, "{terms:?} at {p}");
+            assert!(
+                divergence.abs() < 1e-2 * size,
+                "{divergence} of {terms:?} at {p}"
+            );
+        }
+    }
+}
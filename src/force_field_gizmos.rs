@@ -0,0 +1,89 @@
+use bevy::{color::palettes::basic::*, prelude::*};
+
+use pbf_rs::boundary::box_edges;
+use pbf_rs::force_field::{ForceField, curl_noise};
+use pbf_rs::precision::{Vector, from_vec3, to_f32, to_quat, to_vec3};
+use pbf_rs::simulator::Simulator;
+
+// outlines of the force fields of the current scene, drawn in the tank frame
+pub fn force_field_gizmo_system(mut gizmos: Gizmos, simulator: Res<Simulator>) {
+    let rotation = to_quat(simulator.tank_rotation());
+    let tank = to_vec3(simulator.tank);
+    let time = simulator.time;
+
+    for field in &simulator.force_fields {
+        match field {
+            ForceField::Attractor {
+                center,
+                strength,
+                radius,
+            } => {
+                let color = if *strength >= 0.0 { FUCHSIA } else { MAROON };
+                gizmos.sphere(
+                    Isometry3d::new(rotation * to_vec3(*center), rotation),
+                    to_f32(*radius),
+                    color,
+                );
+            }
+            ForceField::Vortex {
+                center,
+                axis,
+                radius,
+                ..
+            } => {
+                let center = rotation * to_vec3(*center);
+                let Some(axis) = (rotation * to_vec3(*axis)).try_normalize() else {
+                    continue;
+                };
+                let radius = to_f32(*radius);
+                gizmos.line(center - axis * radius, center + axis * radius, AQUA);
+                gizmos.circle(
+                    Isometry3d::new(center, Quat::from_rotation_arc(Vec3::Z, axis)),
+                    radius,
+                    AQUA,
+                );
+            }
+            ForceField::Wind { velocity, .. } => {
+                let Some(dir) = (rotation * to_vec3(*velocity)).try_normalize() else {
+                    continue;
+                };
+                for x in [-0.25, 0.25] {
+                    for z in [-0.25, 0.25] {
+                        let start = rotation * (Vec3::new(x, 0.0, z) * tank);
+                        gizmos.arrow(start, start + dir * 0.2, SILVER);
+                    }
+                }
+            }
+            ForceField::Turbulence {
+                scale, speed, seed, ..
+            } => {
+                // sample the flow direction on a coarse lattice
+                const N: usize = 4;
+                for i in 0..N {
+                    for j in 0..N {
+                        for k in 0..N {
+                            let rel = (Vec3::new(i as f32, j as f32, k as f32) + 0.5) / N as f32;
+                            let local = (rel - 0.5) * tank;
+                            let p = from_vec3(local) / *scale + Vector::splat(time * *speed);
+                            let Some(dir) = to_vec3(curl_noise(p, *seed)).try_normalize() else {
+                                continue;
+                            };
+                            let start = rotation * local;
+                            gizmos.arrow(start, start + rotation * dir * 0.05, OLIVE);
+                        }
+                    }
+                }
+            }
+            ForceField::Drag { min, max, .. } => {
+                let center = (*min + *max) * 0.5;
+                for [a, b] in box_edges((*max - *min) * 0.5) {
+                    gizmos.line(
+                        rotation * to_vec3(center + a),
+                        rotation * to_vec3(center + b),
+                        TEAL,
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,14 @@
+pub mod boundary;
+pub mod cache;
+pub mod checkpoint;
+pub mod diffuse;
+pub mod force_field;
+pub mod material;
+pub mod motion;
+pub mod params;
+pub mod precision;
+pub mod recording;
+pub mod rigid_body;
+pub mod scene_desc;
+pub mod shape_matching;
+pub mod simulator;
@@ -0,0 +1,63 @@
+use std::fs;
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use pbf_rs::scene_desc::SceneDesc;
+
+const SCENE_DIR: &str = "assets/scenes";
+
+// the scenes the switch button cycles through
+#[derive(Resource)]
+pub struct SceneLibrary {
+    scenes: Vec<SceneDesc>,
+    current: usize,
+}
+
+impl SceneLibrary {
+    // a scene file given on the command line comes first, then the builtin
+    // scenes and every .ron file in assets/scenes
+    pub fn load(first: Option<&str>) -> Self {
+        let mut scenes = Vec::new();
+        if let Some(path) = first {
+            match SceneDesc::load(path) {
+                Ok(scene) => scenes.push(scene),
+                Err(err) => eprintln!("failed to load scene {path}: {err}"),
+            }
+        }
+        scenes.extend(SceneDesc::builtin());
+
+        let mut paths: Vec<_> = fs::read_dir(SCENE_DIR)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+            .filter(|path| first.is_none_or(|first| !same_file(path, Path::new(first))))
+            .collect();
+        paths.sort();
+        for path in paths {
+            match SceneDesc::load(&path) {
+                Ok(scene) => scenes.push(scene),
+                Err(err) => eprintln!("failed to load scene {}: {err}", path.display()),
+            }
+        }
+
+        Self { scenes, current: 0 }
+    }
+
+    pub fn current(&self) -> &SceneDesc {
+        &self.scenes[self.current]
+    }
+
+    pub fn next(&mut self) -> &SceneDesc {
+        self.current = (self.current + 1) % self.scenes.len();
+        self.current()
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
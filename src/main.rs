@@ -1,42 +1,170 @@
+use bevy::input::InputSystem;
 use bevy::prelude::*;
 use bevy::text::FontSmoothing;
 use bevy_dev_tools::fps_overlay::{FpsOverlayConfig, FpsOverlayPlugin};
 
+use pbf_rs::params::SimParams;
+use pbf_rs::simulator::Simulator;
+
+use crate::cache_playback::{CachePlayback, cache_playback_system};
+use crate::camera::{OrbitCamera, camera_control_system};
+use crate::capture::{Capture, capture_system, setup_capture_text};
+use crate::diffuse_render::{diffuse_export_system, diffuse_render_system, setup_diffuse_assets};
+use crate::force_field_gizmos::force_field_gizmo_system;
+use crate::library::SceneLibrary;
+use crate::panel::{
+    ValueEntry, knob_slider_system, param_panel_refresh_system, param_panel_toggle_system,
+    setup_param_panel, value_entry_capture_system, value_entry_system,
+};
+use crate::particles::{
+    ImpostorMaterial, ParticleRender, particle_detail_system, render_mode_system,
+};
+use crate::picking::{
+    PickSettings, grab_gizmo_system, pick_settings_system, pick_system, setup_pick_settings_text,
+};
+use crate::rigid_body_sync::{rigid_body_spawn_system, rigid_body_sync_system};
 use crate::scene::{
-    camera_control_system, pause_resume_button_system, reset_sim_button_system, scene_refresh_system, setup, simulation_step, switch_scene_button_system, update_boundary
+    pause_resume_button_system, reset_sim_button_system, scene_refresh_system, setup,
+    simulation_step, switch_scene_button_system, update_boundary,
+};
+use crate::stepping::{Stepper, setup_stepper_text, stepping_gizmo_system, stepping_system};
+use crate::tilt::{TiltControl, gravity_gizmo_system, tilt_control_system};
+use crate::timeline::{
+    Timeline, record_system, setup_timeline, timeline_control_system, timeline_refresh_system,
 };
-use crate::simulator::Simulator;
 
+mod cache_playback;
+mod camera;
+mod capture;
+mod diffuse_render;
+mod force_field_gizmos;
+mod library;
+mod panel;
+mod particles;
+mod picking;
+mod rigid_body_sync;
 mod scene;
-mod simulator;
+mod stepping;
+mod tilt;
+mod timeline;
 
 fn main() {
-    App::new()
-        .add_plugins((
-            DefaultPlugins,
-            FpsOverlayPlugin {
-                config: FpsOverlayConfig {
-                    text_config: TextFont {
-                        font_size: 20.0,
-                        font: default(),
-                        font_smoothing: FontSmoothing::default(),
-                        ..default()
-                    },
-                    text_color: Color::srgb(0.0, 1.0, 0.0),
-                    refresh_interval: core::time::Duration::from_millis(100),
-                    enabled: true,
+    // an optional scene file to start with, and a particle cache written by pbf-cli from it
+    // to play back
+    let mut scene = None;
+    let mut cache = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cache" => cache = args.next(),
+            _ => scene = Some(arg),
+        }
+    }
+    let library = SceneLibrary::load(scene.as_deref());
+    let mut simulator = Simulator::new(SimParams::default());
+    simulator.set_scene(library.current().clone());
+    let cache = cache.and_then(|path| match CachePlayback::open(&path) {
+        Ok(cache) => Some(cache),
+        Err(err) => {
+            eprintln!("failed to open particle cache {path}: {err}");
+            None
+        }
+    });
+
+    let mut app = App::new();
+    app.add_plugins((
+        DefaultPlugins,
+        FpsOverlayPlugin {
+            config: FpsOverlayConfig {
+                text_config: TextFont {
+                    font_size: 20.0,
+                    font: default(),
+                    font_smoothing: FontSmoothing::default(),
+                    ..default()
                 },
+                text_color: Color::srgb(0.0, 1.0, 0.0),
+                refresh_interval: core::time::Duration::from_millis(100),
+                enabled: true,
             },
-        ))
-        .insert_resource(Simulator::new())
-        .insert_resource(scene::SimRunning(true))
-        .add_systems(Startup, setup)
-        .add_systems(Update, camera_control_system)
-        .add_systems(Update, pause_resume_button_system)
-        .add_systems(Update, switch_scene_button_system)
-        .add_systems(Update, reset_sim_button_system)
-        .add_systems(Update, scene_refresh_system)
-        .add_systems(Update, update_boundary)
-        .add_systems(PostUpdate, simulation_step)
-        .run();
+        },
+        MaterialPlugin::<ImpostorMaterial> {
+            prepass_enabled: false,
+            shadows_enabled: false,
+            ..default()
+        },
+    ))
+    .insert_resource(simulator)
+    .insert_resource(library)
+    .insert_resource(scene::SimRunning(true))
+    .init_resource::<TiltControl>()
+    .init_resource::<PickSettings>()
+    .init_resource::<Capture>()
+    .init_resource::<Timeline>()
+    .init_resource::<Stepper>()
+    .init_resource::<ParticleRender>()
+    .init_resource::<ValueEntry>()
+    .insert_resource(OrbitCamera::load())
+    .add_systems(Startup, setup)
+    .add_systems(Startup, setup_param_panel)
+    .add_systems(Startup, setup_pick_settings_text)
+    .add_systems(Startup, setup_diffuse_assets)
+    .add_systems(Startup, setup_capture_text)
+    .add_systems(Startup, setup_timeline)
+    .add_systems(Startup, setup_stepper_text)
+    .add_systems(PreUpdate, value_entry_capture_system.after(InputSystem))
+    .add_systems(Update, camera_control_system)
+    .add_systems(Update, pause_resume_button_system)
+    .add_systems(Update, switch_scene_button_system)
+    .add_systems(Update, reset_sim_button_system)
+    .add_systems(Update, scene_refresh_system)
+    .add_systems(Update, (tilt_control_system, update_boundary).chain())
+    .add_systems(Update, gravity_gizmo_system)
+    .add_systems(Update, force_field_gizmo_system)
+    .add_systems(
+        Update,
+        (pick_settings_system, pick_system, grab_gizmo_system).chain(),
+    )
+    .add_systems(Update, param_panel_toggle_system)
+    .add_systems(
+        Update,
+        (
+            knob_slider_system,
+            value_entry_system,
+            param_panel_refresh_system,
+        )
+            .chain(),
+    )
+    .add_systems(Update, timeline_control_system)
+    .add_systems(Update, cache_playback_system)
+    .add_systems(Update, (stepping_system, stepping_gizmo_system).chain())
+    .add_systems(Update, render_mode_system)
+    .add_systems(PostUpdate, simulation_step)
+    .add_systems(PostUpdate, particle_detail_system.after(simulation_step))
+    .add_systems(
+        PostUpdate,
+        (record_system, timeline_refresh_system)
+            .chain()
+            .after(simulation_step),
+    )
+    .add_systems(
+        PostUpdate,
+        (rigid_body_spawn_system, rigid_body_sync_system)
+            .chain()
+            .after(simulation_step),
+    )
+    .add_systems(
+        PostUpdate,
+        (diffuse_render_system, diffuse_export_system).after(simulation_step),
+    )
+    // after everything moved, so the frame shows the state it is named for
+    .add_systems(
+        PostUpdate,
+        capture_system
+            .after(rigid_body_sync_system)
+            .after(diffuse_render_system),
+    );
+    if let Some(cache) = cache {
+        app.insert_resource(cache);
+    }
+    app.run();
 }
@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+use crate::precision::{Real, Vector, vector};
+
+// what a particle is made of
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Material {
+    #[default]
+    Fluid,
+    // sand and gravel, particles resolve contacts as non-penetration constraints
+    // and stick to each other and the walls with coulomb friction
+    Granular {
+        static_friction: Real,
+        kinetic_friction: Real,
+    },
+}
+
+impl Material {
+    pub fn sand() -> Self {
+        Material::Granular {
+            static_friction: 0.6,
+            kinetic_friction: 0.5,
+        }
+    }
+
+    pub fn is_fluid(&self) -> bool {
+        matches!(self, Material::Fluid)
+    }
+
+    // display color in srgb
+    pub fn color(&self) -> Vector {
+        match self {
+            Material::Fluid => vector(0.0, 30.0 / 255.0, 1.0),
+            Material::Granular { .. } => vector(0.76, 0.6, 0.36),
+        }
+    }
+}
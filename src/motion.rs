@@ -0,0 +1,276 @@
+use bevy::math::EulerRot;
+use serde::{Deserialize, Serialize};
+
+use crate::precision::{Real, Rotation, Vector};
+
+const TAU: Real = 2.0 * std::f64::consts::PI as Real;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub time: Real,
+    pub value: Vector,
+}
+
+impl Keyframe {
+    pub fn new(time: Real, value: Vector) -> Self {
+        Self { time, value }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interpolation {
+    Linear,
+    CatmullRom, // uniform Catmull-Rom spline through the keyframes
+}
+
+// a vector valued curve over simulated time
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Track {
+    Constant(Vector),
+    // keys must be sorted by time, a looping track repeats from the first to the last key
+    Keyframes {
+        keys: Vec<Keyframe>,
+        interpolation: Interpolation,
+        looping: bool,
+    },
+    // offset + amplitude * sin(2 pi frequency t + phase), per component
+    Sinusoid {
+        offset: Vector,
+        amplitude: Vector,
+        frequency: Real,
+        phase: Real,
+    },
+}
+
+impl Default for Track {
+    fn default() -> Self {
+        Track::Constant(Vector::ZERO)
+    }
+}
+
+impl Track {
+    pub fn sample(&self, t: Real) -> Vector {
+        match self {
+            Track::Constant(value) => *value,
+            Track::Keyframes {
+                keys,
+                interpolation,
+                looping,
+            } => sample_keyframes(keys, *interpolation, *looping, t),
+            Track::Sinusoid {
+                offset,
+                amplitude,
+                frequency,
+                phase,
+            } => *offset + *amplitude * (TAU * frequency * t + phase).sin(),
+        }
+    }
+}
+
+fn sample_keyframes(
+    keys: &[Keyframe],
+    interpolation: Interpolation,
+    looping: bool,
+    t: Real,
+) -> Vector {
+    let (first, last) = match (keys.first(), keys.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Vector::ZERO,
+    };
+    let duration = last.time - first.time;
+    if keys.len() == 1 || duration <= 0.0 {
+        return first.value;
+    }
+
+    let t = if looping {
+        first.time + (t - first.time).rem_euclid(duration)
+    } else if t <= first.time {
+        return first.value;
+    } else if t >= last.time {
+        return last.value;
+    } else {
+        t
+    };
+
+    // segment [i, i + 1] containing t
+    let i = keys
+        .windows(2)
+        .position(|w| t < w[1].time)
+        .unwrap_or(keys.len() - 2);
+    let (k1, k2) = (keys[i], keys[i + 1]);
+    let span = k2.time - k1.time;
+    let u = if span > 0.0 {
+        (t - k1.time) / span
+    } else {
+        0.0
+    };
+
+    match interpolation {
+        Interpolation::Linear => k1.value.lerp(k2.value, u),
+        Interpolation::CatmullRom => {
+            let n = keys.len();
+            // a looping track treats the first and last key as the same point
+            let p0 = if i > 0 {
+                keys[i - 1].value
+            } else if looping {
+                keys[n - 2].value
+            } else {
+                k1.value
+            };
+            let p3 = if i + 2 < n {
+                keys[i + 2].value
+            } else if looping {
+                keys[1].value
+            } else {
+                k2.value
+            };
+            let (p1, p2) = (k1.value, k2.value);
+            let u2 = u * u;
+            let u3 = u2 * u;
+            0.5 * ((2.0 * p1)
+                + (p2 - p0) * u
+                + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * u2
+                + (3.0 * p1 - p0 - 3.0 * p2 + p3) * u3)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pose {
+    pub translation: Vector,
+    pub rotation: Rotation,
+}
+
+impl Default for Pose {
+    fn default() -> Self {
+        Self {
+            translation: Vector::ZERO,
+            rotation: Rotation::IDENTITY,
+        }
+    }
+}
+
+impl Pose {
+    pub fn transform_point(&self, p: Vector) -> Vector {
+        self.rotation * p + self.translation
+    }
+
+    pub fn inverse_transform_point(&self, p: Vector) -> Vector {
+        self.rotation.inverse() * (p - self.translation)
+    }
+}
+
+// translation and rotation curves of a moving boundary
+// rotation is sampled as XYZ euler angles in radians
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Motion {
+    pub translation: Track,
+    pub rotation: Track,
+}
+
+impl Motion {
+    pub fn fixed(translation: Vector) -> Self {
+        Self {
+            translation: Track::Constant(translation),
+            rotation: Track::Constant(Vector::ZERO),
+        }
+    }
+
+    pub fn pose(&self, t: Real) -> Pose {
+        let angles = self.rotation.sample(t);
+        Pose {
+            translation: self.translation.sample(t),
+            rotation: Rotation::from_euler(EulerRot::XYZ, angles.x, angles.y, angles.z),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::precision::vector;
+
+    fn keyframes(interpolation: Interpolation, looping: bool) -> Track {
+        Track::Keyframes {
+            keys: vec![
+                Keyframe::new(0.0, vector(0.0, 0.0, 0.0)),
+                Keyframe::new(1.0, vector(1.0, 2.0, 0.0)),
+                Keyframe::new(3.0, vector(-1.0, 0.5, 2.0)),
+                Keyframe::new(4.0, vector(0.0, 0.0, 0.0)),
+            ],
+            interpolation,
+            looping,
+        }
+    }
+
+    fn close(a: Vector, b: Vector, tolerance: Real) -> bool {
+        (a - b).abs().max_element() <= tolerance
+    }
+
+    #[test]
+    fn keyframes_are_hit_exactly() {
+        for interpolation in [Interpolation::Linear, Interpolation::CatmullRom] {
+            for looping in [false, true] {
+                let track = keyframes(interpolation, looping);
+                let Track::Keyframes { keys, .. } = &track else {
+                    unreachable!()
+                };
+                for key in keys {
+                    assert_eq!(
+                        track.sample(key.time),
+                        key.value,
+                        "{interpolation:?} at {}",
+                        key.time
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn linear_midpoint_is_the_average_of_the_keys() {
+        let track = keyframes(Interpolation::Linear, false);
+        let expected = 0.5 * (vector(1.0, 2.0, 0.0) + vector(-1.0, 0.5, 2.0));
+        assert!(close(track.sample(2.0), expected, 1e-6));
+        // held at the ends without looping
+        assert_eq!(track.sample(-1.0), Vector::ZERO);
+        assert_eq!(track.sample(5.0), Vector::ZERO);
+    }
+
+    #[test]
+    fn catmull_rom_loop_is_smooth_across_the_wrap() {
+        let track = keyframes(Interpolation::CatmullRom, true);
+        let e = 1e-3;
+        // the same position and slope just before the end of the loop and just after
+        // its start
+        assert!(close(track.sample(4.0 - e), track.sample(-e), 1e-5));
+        let before = (track.sample(4.0) - track.sample(4.0 - e)) / e;
+        let after = (track.sample(e) - track.sample(0.0)) / e;
+        assert!(close(before, after, 1e-2), "{before} {after}");
+        // the slope at the wrap points from the key before the end to the second key
+        let slope = 0.5 * (vector(1.0, 2.0, 0.0) - vector(-1.0, 0.5, 2.0));
+        assert!(close(after, slope, 1e-2), "{after} {slope}");
+    }
+
+    #[test]
+    fn sinusoid_velocity_matches_the_finite_difference() {
+        let (offset, amplitude) = (vector(0.1, 0.0, -0.2), vector(0.3, 0.0, 0.05));
+        let (frequency, phase) = (0.7, 0.4);
+        let track = Track::Sinusoid {
+            offset,
+            amplitude,
+            frequency,
+            phase,
+        };
+        let e = 1e-3;
+        for t in [0.0, 0.3, 1.1, 2.5] {
+            let velocity = amplitude * TAU * frequency * (TAU * frequency * t + phase).cos();
+            let difference = (track.sample(t + e) - track.sample(t - e)) / (2.0 * e);
+            assert!(
+                close(difference, velocity, 1e-3),
+                "{difference} {velocity} at {t}"
+            );
+        }
+    }
+}
@@ -0,0 +1,531 @@
+use bevy::{
+    color::palettes::basic::*,
+    input::keyboard::{Key, KeyboardInput},
+    prelude::*,
+    ui::RelativeCursorPosition,
+};
+
+use pbf_rs::params::{SimParams, SimParamsBuilder};
+use pbf_rs::precision::{Real, to_f32};
+use pbf_rs::simulator::Simulator;
+
+use crate::scene::{HOVERED_BUTTON, NORMAL_BUTTON};
+
+const PANEL_BACKGROUND: Color = Color::srgba(0.05, 0.05, 0.05, 0.8);
+const TRACK_COLOR: Color = Color::srgb(0.2, 0.2, 0.2);
+const FILL_COLOR: Color = Color::srgb(0.0, 30.0 / 255.0, 1.0);
+const MODIFIED_COLOR: Color = Color::srgb(1.0, 0.8, 0.2);
+const EDITING_COLOR: Color = Color::srgb(0.3, 0.3, 0.3);
+
+// a solver parameter exposed in the side panel
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Knob {
+    GravityX,
+    GravityY,
+    GravityZ,
+    SolverIterations,
+    Relaxation,
+    Damping,
+    TensileK,
+    TensileN,
+    TimeStep,
+    Radius,
+}
+
+impl Knob {
+    const ALL: [Knob; 10] = [
+        Knob::GravityX,
+        Knob::GravityY,
+        Knob::GravityZ,
+        Knob::SolverIterations,
+        Knob::Relaxation,
+        Knob::Damping,
+        Knob::TensileK,
+        Knob::TensileN,
+        Knob::TimeStep,
+        Knob::Radius,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Knob::GravityX => "Gravity X",
+            Knob::GravityY => "Gravity Y",
+            Knob::GravityZ => "Gravity Z",
+            Knob::SolverIterations => "Iterations",
+            Knob::Relaxation => "Relaxation",
+            Knob::Damping => "Damping",
+            Knob::TensileK => "Tensile K",
+            Knob::TensileN => "Tensile N",
+            Knob::TimeStep => "Time Step",
+            Knob::Radius => "Radius",
+        }
+    }
+
+    // slider range, log scaled knobs span several orders of magnitude
+    fn range(self) -> (Real, Real) {
+        match self {
+            Knob::GravityX | Knob::GravityY | Knob::GravityZ => (-30.0, 30.0),
+            Knob::SolverIterations => (1.0, 20.0),
+            Knob::Relaxation => (1.0, 1e5),
+            Knob::Damping => (0.9, 1.0),
+            Knob::TensileK => (1e-7, 1e-3),
+            Knob::TensileN => (1.0, 8.0),
+            Knob::TimeStep => (1.0 / 1000.0, 1.0 / 60.0),
+            Knob::Radius => (0.008, 0.04),
+        }
+    }
+
+    fn log_scale(self) -> bool {
+        matches!(self, Knob::Relaxation | Knob::TensileK)
+    }
+
+    fn integer(self) -> bool {
+        matches!(self, Knob::SolverIterations | Knob::TensileN)
+    }
+
+    // knobs which only take effect after the particles are rebuilt
+    fn needs_reset(self) -> bool {
+        matches!(self, Knob::Radius)
+    }
+
+    fn get(self, params: &SimParams) -> Real {
+        match self {
+            Knob::GravityX => params.gravity().x,
+            Knob::GravityY => params.gravity().y,
+            Knob::GravityZ => params.gravity().z,
+            Knob::SolverIterations => params.solver_iterations() as Real,
+            Knob::Relaxation => params.relaxation(),
+            Knob::Damping => params.damping(),
+            Knob::TensileK => params.tensile_k(),
+            Knob::TensileN => params.tensile_n() as Real,
+            Knob::TimeStep => params.time_step(),
+            Knob::Radius => params.radius(),
+        }
+    }
+
+    fn set(self, builder: SimParamsBuilder, params: &SimParams, value: Real) -> SimParamsBuilder {
+        match self {
+            Knob::GravityX | Knob::GravityY | Knob::GravityZ => {
+                let axis = match self {
+                    Knob::GravityX => 0,
+                    Knob::GravityY => 1,
+                    _ => 2,
+                };
+                let mut gravity = params.gravity();
+                gravity[axis] = value;
+                builder.gravity(gravity)
+            }
+            Knob::SolverIterations => builder.solver_iterations(value as usize),
+            Knob::Relaxation => builder.relaxation(value),
+            Knob::Damping => builder.damping(value),
+            Knob::TensileK => builder.tensile_k(value),
+            Knob::TensileN => builder.tensile_n(value as i32),
+            Knob::TimeStep => builder.time_step(value),
+            Knob::Radius => builder.radius(value),
+        }
+    }
+
+    fn format(self, value: Real) -> String {
+        match self {
+            Knob::SolverIterations | Knob::TensileN => format!("{}", value as i64),
+            Knob::Relaxation | Knob::TensileK => format!("{value:.1e}"),
+            Knob::Damping => format!("{value:.4}"),
+            Knob::TimeStep => format!("1/{:.0}", 1.0 / value),
+            Knob::GravityX | Knob::GravityY | Knob::GravityZ => format!("{value:.2}"),
+            Knob::Radius => format!("{value:.3}"),
+        }
+    }
+
+    // a typed value, a plain number or a fraction like 1/200
+    fn parse(self, text: &str) -> Option<Real> {
+        let value = match text.split_once('/') {
+            Some((num, den)) => {
+                num.trim().parse::<Real>().ok()? / den.trim().parse::<Real>().ok()?
+            }
+            None => text.trim().parse().ok()?,
+        };
+        if self.integer() {
+            (value >= 0.0 && value.fract() == 0.0).then_some(value)
+        } else {
+            Some(value)
+        }
+    }
+
+    // slider position in [0, 1] to parameter value
+    fn value_at(self, t: Real) -> Real {
+        let (min, max) = self.range();
+        let t = t.clamp(0.0, 1.0);
+        let value = if self.log_scale() {
+            min * (max / min).powf(t)
+        } else {
+            min + (max - min) * t
+        };
+        if self.integer() { value.round() } else { value }
+    }
+
+    // parameter value to slider position in [0, 1]
+    fn slider_position(self, value: Real) -> Real {
+        let (min, max) = self.range();
+        let t = if self.log_scale() {
+            (value.max(min) / min).ln() / (max / min).ln()
+        } else {
+            (value - min) / (max - min)
+        };
+        t.clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Component)]
+pub struct ParamPanelToggle;
+
+#[derive(Component)]
+pub struct ParamPanelBody;
+
+#[derive(Component)]
+pub struct KnobSlider(Knob);
+
+#[derive(Component)]
+pub struct KnobFill(Knob);
+
+#[derive(Component)]
+pub struct KnobLabel(Knob);
+
+#[derive(Component)]
+pub struct KnobValue(Knob);
+
+// the box around a value, clicking it starts typing a new value
+#[derive(Component)]
+pub struct KnobField(Knob);
+
+// the value being typed into a knob's field, Enter applies it and Escape drops it
+#[derive(Resource, Default)]
+pub struct ValueEntry {
+    editing: Option<(Knob, String)>,
+}
+
+impl ValueEntry {
+    pub fn is_editing(&self) -> bool {
+        self.editing.is_some()
+    }
+}
+
+fn knob_row(knob: Knob, font: &Handle<Font>) -> impl Bundle {
+    (
+        Node {
+            width: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            margin: UiRect::top(Val::Px(8.0)),
+            ..default()
+        },
+        children![
+            (
+                Node {
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::SpaceBetween,
+                    ..default()
+                },
+                children![
+                    (
+                        Text::new(knob.label()),
+                        TextFont {
+                            font: font.clone(),
+                            font_size: 16.0,
+                            ..default()
+                        },
+                        TextColor(Color::WHITE),
+                        KnobLabel(knob),
+                    ),
+                    (
+                        Button,
+                        KnobField(knob),
+                        Node {
+                            padding: UiRect::horizontal(Val::Px(4.0)),
+                            ..default()
+                        },
+                        BorderRadius::all(Val::Px(4.0)),
+                        BackgroundColor(Color::NONE),
+                        children![(
+                            Text::new(""),
+                            TextFont {
+                                font: font.clone(),
+                                font_size: 16.0,
+                                ..default()
+                            },
+                            TextColor(Color::WHITE),
+                            KnobValue(knob),
+                        )]
+                    )
+                ]
+            ),
+            (
+                Button,
+                KnobSlider(knob),
+                RelativeCursorPosition::default(),
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Px(14.0),
+                    margin: UiRect::top(Val::Px(4.0)),
+                    ..default()
+                },
+                BorderRadius::all(Val::Px(4.0)),
+                BackgroundColor(TRACK_COLOR),
+                children![(
+                    KnobFill(knob),
+                    Node {
+                        width: Val::Percent(0.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    BorderRadius::all(Val::Px(4.0)),
+                    BackgroundColor(FILL_COLOR),
+                )]
+            )
+        ],
+    )
+}
+
+pub fn setup_param_panel(mut commands: Commands, assets: Res<AssetServer>) {
+    let font: Handle<Font> = assets.load("fonts/FiraSans-Bold.ttf");
+
+    let body = commands
+        .spawn((
+            ParamPanelBody,
+            Node {
+                width: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(12.0)),
+                margin: UiRect::top(Val::Px(8.0)),
+                display: Display::None,
+                ..default()
+            },
+            BorderRadius::all(Val::Px(8.0)),
+            BackgroundColor(PANEL_BACKGROUND),
+        ))
+        .id();
+    for knob in Knob::ALL {
+        let row = commands.spawn(knob_row(knob, &font)).id();
+        commands.entity(body).add_child(row);
+    }
+
+    let toggle = commands
+        .spawn((
+            Button,
+            ParamPanelToggle,
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Px(50.0),
+                border: UiRect::all(Val::Px(5.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BorderColor(Color::BLACK),
+            BorderRadius::MAX,
+            BackgroundColor(NORMAL_BUTTON),
+            children![(
+                Text::new("Show Parameters"),
+                TextFont {
+                    font: font.clone(),
+                    font_size: 20.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                TextShadow::default(),
+            )],
+        ))
+        .id();
+
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(20.0),
+            top: Val::Px(20.0),
+            width: Val::Px(300.0),
+            flex_direction: FlexDirection::Column,
+            ..default()
+        })
+        .add_children(&[toggle, body]);
+}
+
+#[allow(clippy::type_complexity)]
+pub fn param_panel_toggle_system(
+    mut interaction_query: Query<
+        (
+            &Interaction,
+            &mut BackgroundColor,
+            &mut BorderColor,
+            &Children,
+        ),
+        (Changed<Interaction>, With<ParamPanelToggle>),
+    >,
+    mut text_query: Query<&mut Text>,
+    mut body_query: Query<&mut Node, With<ParamPanelBody>>,
+) {
+    for (interaction, mut color, mut border_color, children) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                let mut body = body_query.single_mut().unwrap();
+                let mut text = text_query.get_mut(children[0]).unwrap();
+                if body.display == Display::None {
+                    body.display = Display::Flex;
+                    **text = "Hide Parameters".to_string();
+                } else {
+                    body.display = Display::None;
+                    **text = "Show Parameters".to_string();
+                }
+                *color = GREEN.into();
+                border_color.0 = GREEN.into();
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON.into();
+                border_color.0 = Color::WHITE;
+            }
+            Interaction::None => {
+                *color = NORMAL_BUTTON.into();
+                border_color.0 = Color::BLACK;
+            }
+        }
+    }
+}
+
+pub fn knob_slider_system(
+    query: Query<(&Interaction, &RelativeCursorPosition, &KnobSlider)>,
+    mut simulator: ResMut<Simulator>,
+) {
+    for (interaction, cursor, slider) in &query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some(normalized) = cursor.normalized else {
+            continue;
+        };
+        let knob = slider.0;
+        let value = knob.value_at(normalized.x as Real);
+        if value == knob.get(simulator.params()) {
+            continue;
+        }
+        let params = simulator.params();
+        // out of range values are rejected by the builder and leave the parameters untouched
+        if let Ok(params) = knob.set(params.to_builder(), params, value).build() {
+            simulator.set_params(params);
+        }
+    }
+}
+
+pub fn param_panel_refresh_system(
+    simulator: Res<Simulator>,
+    entry: Res<ValueEntry>,
+    mut fill_query: Query<(&KnobFill, &mut Node)>,
+    mut field_query: Query<(&KnobField, &mut BackgroundColor)>,
+    mut value_query: Query<(&KnobValue, &mut Text, &mut TextColor), Without<KnobLabel>>,
+    mut label_query: Query<(&KnobLabel, &mut TextColor), Without<KnobValue>>,
+) {
+    if !simulator.is_changed() && !entry.is_changed() {
+        return;
+    }
+    let params = simulator.params();
+    let defaults = SimParams::default();
+
+    for (fill, mut node) in &mut fill_query {
+        let t = fill.0.slider_position(fill.0.get(params));
+        node.width = Val::Percent(100.0 * to_f32(t));
+    }
+
+    let editing = entry.editing.as_ref();
+    for (field, mut background) in &mut field_query {
+        let edited = editing.is_some_and(|(knob, _)| *knob == field.0);
+        background.0 = if edited { EDITING_COLOR } else { Color::NONE };
+    }
+
+    for (value, mut text, mut color) in &mut value_query {
+        let knob = value.0;
+        if let Some((_, typed)) = editing.filter(|(edited, _)| *edited == knob) {
+            **text = format!("{typed}_");
+            color.0 = Color::WHITE;
+            continue;
+        }
+        let current = knob.get(params);
+        // rebuild parameters are applied on reset, mark them until then
+        let pending = knob.needs_reset() && current != simulator.radius;
+        **text = if pending {
+            format!("{} (on reset)", knob.format(current))
+        } else {
+            knob.format(current)
+        };
+        color.0 = if pending { RED.into() } else { Color::WHITE };
+    }
+
+    for (label, mut color) in &mut label_query {
+        let knob = label.0;
+        color.0 = if knob.get(params) != knob.get(&defaults) {
+            MODIFIED_COLOR
+        } else {
+            Color::WHITE
+        };
+    }
+}
+
+// clicking a value starts typing over it, Enter applies the typed value and Escape or
+// clicking elsewhere drops it, values the parameters reject are dropped too
+pub fn value_entry_system(
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut keyboard_events: EventReader<KeyboardInput>,
+    field_query: Query<(&Interaction, &KnobField)>,
+    mut entry: ResMut<ValueEntry>,
+    mut simulator: ResMut<Simulator>,
+) {
+    if mouse.just_pressed(MouseButton::Left) {
+        let clicked = field_query
+            .iter()
+            .find(|(interaction, _)| **interaction == Interaction::Pressed)
+            .map(|(_, field)| (field.0, String::new()));
+        if clicked.is_some() || entry.is_editing() {
+            entry.editing = clicked;
+        }
+    }
+
+    let Some((knob, mut text)) = entry.editing.clone() else {
+        keyboard_events.clear();
+        return;
+    };
+    for event in keyboard_events.read() {
+        if !event.state.is_pressed() {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Enter => {
+                let params = simulator.params();
+                let applied = knob
+                    .parse(&text)
+                    .and_then(|value| knob.set(params.to_builder(), params, value).build().ok());
+                match applied {
+                    Some(params) => simulator.set_params(params),
+                    None => warn!("ignoring {:?} for {}", text, knob.label()),
+                }
+                entry.editing = None;
+                return;
+            }
+            Key::Escape => {
+                entry.editing = None;
+                return;
+            }
+            Key::Backspace => {
+                text.pop();
+            }
+            Key::Character(typed) => {
+                text.extend(typed.chars().filter(|c| "0123456789.-+eE/".contains(*c)));
+            }
+            _ => {}
+        }
+    }
+    if entry.editing.as_ref().is_some_and(|(_, old)| *old != text) {
+        entry.editing = Some((knob, text));
+    }
+}
+
+// the keys typed into a value do not reach the other controls
+pub fn value_entry_capture_system(entry: Res<ValueEntry>, mut keys: ResMut<ButtonInput<KeyCode>>) {
+    if entry.is_editing() {
+        keys.reset_all();
+    }
+}
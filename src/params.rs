@@ -0,0 +1,283 @@
+use std::error::Error;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::precision::{Real, Vector};
+
+// solver configuration, built and validated through SimParamsBuilder
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SimParams {
+    radius: Real,          // radius of particles
+    ratio: Real,           // ratio between max neighbor distance and particle radius
+    rest_neighbors: usize, // neighbors at twice the radius used to derive the rest density
+    solver_iterations: usize,
+    relaxation: Real, // constraint force mixing added to the lambda denominator
+    damping: Real,
+    gravity: Vector,
+    tensile_k: Real,  // artificial pressure strength
+    tensile_n: i32,   // artificial pressure exponent
+    tensile_dq: Real, // artificial pressure reference distance, relative to h
+    time_step: Real,
+}
+
+impl Default for SimParams {
+    fn default() -> Self {
+        Self {
+            radius: 0.015,
+            ratio: 3.0,
+            rest_neighbors: 9,
+            solver_iterations: 5,
+            relaxation: 1e3,
+            damping: 0.999,
+            gravity: Vector::new(0.0, -9.81, 0.0),
+            tensile_k: 1e-5,
+            tensile_n: 4,
+            tensile_dq: 0.3,
+            time_step: 1.0 / 200.0,
+        }
+    }
+}
+
+impl SimParams {
+    pub fn builder() -> SimParamsBuilder {
+        SimParamsBuilder::default()
+    }
+
+    // a builder starting from these parameters
+    pub fn to_builder(&self) -> SimParamsBuilder {
+        SimParamsBuilder {
+            params: self.clone(),
+        }
+    }
+
+    pub fn radius(&self) -> Real {
+        self.radius
+    }
+
+    pub fn ratio(&self) -> Real {
+        self.ratio
+    }
+
+    pub fn rest_neighbors(&self) -> usize {
+        self.rest_neighbors
+    }
+
+    pub fn solver_iterations(&self) -> usize {
+        self.solver_iterations
+    }
+
+    pub fn relaxation(&self) -> Real {
+        self.relaxation
+    }
+
+    pub fn damping(&self) -> Real {
+        self.damping
+    }
+
+    pub fn gravity(&self) -> Vector {
+        self.gravity
+    }
+
+    pub fn tensile_k(&self) -> Real {
+        self.tensile_k
+    }
+
+    pub fn tensile_n(&self) -> i32 {
+        self.tensile_n
+    }
+
+    pub fn tensile_dq(&self) -> Real {
+        self.tensile_dq
+    }
+
+    pub fn time_step(&self) -> Real {
+        self.time_step
+    }
+
+    fn validate(&self) -> Result<(), SimParamsError> {
+        if !(self.radius > 0.0 && self.radius.is_finite()) {
+            return Err(SimParamsError::Radius(self.radius));
+        }
+        // below a ratio of 2 particles at rest spacing are not neighbors
+        if !(self.ratio > 2.0 && self.ratio.is_finite()) {
+            return Err(SimParamsError::Ratio(self.ratio));
+        }
+        if self.rest_neighbors == 0 {
+            return Err(SimParamsError::RestNeighbors);
+        }
+        if self.solver_iterations == 0 {
+            return Err(SimParamsError::SolverIterations);
+        }
+        if !(self.relaxation > 0.0 && self.relaxation.is_finite()) {
+            return Err(SimParamsError::Relaxation(self.relaxation));
+        }
+        if !(self.damping > 0.0 && self.damping <= 1.0) {
+            return Err(SimParamsError::Damping(self.damping));
+        }
+        if !self.gravity.is_finite() {
+            return Err(SimParamsError::Gravity(self.gravity));
+        }
+        if !(self.tensile_k >= 0.0 && self.tensile_k.is_finite()) {
+            return Err(SimParamsError::TensileK(self.tensile_k));
+        }
+        if self.tensile_n < 1 {
+            return Err(SimParamsError::TensileN(self.tensile_n));
+        }
+        if !(self.tensile_dq > 0.0 && self.tensile_dq < 1.0) {
+            return Err(SimParamsError::TensileDq(self.tensile_dq));
+        }
+        if !(self.time_step > 0.0 && self.time_step.is_finite()) {
+            return Err(SimParamsError::TimeStep(self.time_step));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SimParamsBuilder {
+    params: SimParams,
+}
+
+impl SimParamsBuilder {
+    pub fn radius(mut self, radius: Real) -> Self {
+        self.params.radius = radius;
+        self
+    }
+
+    pub fn ratio(mut self, ratio: Real) -> Self {
+        self.params.ratio = ratio;
+        self
+    }
+
+    pub fn rest_neighbors(mut self, rest_neighbors: usize) -> Self {
+        self.params.rest_neighbors = rest_neighbors;
+        self
+    }
+
+    pub fn solver_iterations(mut self, solver_iterations: usize) -> Self {
+        self.params.solver_iterations = solver_iterations;
+        self
+    }
+
+    pub fn relaxation(mut self, relaxation: Real) -> Self {
+        self.params.relaxation = relaxation;
+        self
+    }
+
+    pub fn damping(mut self, damping: Real) -> Self {
+        self.params.damping = damping;
+        self
+    }
+
+    pub fn gravity(mut self, gravity: Vector) -> Self {
+        self.params.gravity = gravity;
+        self
+    }
+
+    pub fn tensile_k(mut self, tensile_k: Real) -> Self {
+        self.params.tensile_k = tensile_k;
+        self
+    }
+
+    pub fn tensile_n(mut self, tensile_n: i32) -> Self {
+        self.params.tensile_n = tensile_n;
+        self
+    }
+
+    pub fn tensile_dq(mut self, tensile_dq: Real) -> Self {
+        self.params.tensile_dq = tensile_dq;
+        self
+    }
+
+    pub fn time_step(mut self, time_step: Real) -> Self {
+        self.params.time_step = time_step;
+        self
+    }
+
+    pub fn build(self) -> Result<SimParams, SimParamsError> {
+        self.params.validate()?;
+        Ok(self.params)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SimParamsError {
+    Radius(Real),
+    Ratio(Real),
+    RestNeighbors,
+    SolverIterations,
+    Relaxation(Real),
+    Damping(Real),
+    Gravity(Vector),
+    TensileK(Real),
+    TensileN(i32),
+    TensileDq(Real),
+    TimeStep(Real),
+}
+
+impl fmt::Display for SimParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Radius(v) => write!(f, "particle radius must be positive, got {v}"),
+            Self::Ratio(v) => write!(f, "neighbor ratio must be greater than 2, got {v}"),
+            Self::RestNeighbors => write!(f, "rest neighbor count must be positive"),
+            Self::SolverIterations => write!(f, "solver iterations must be positive"),
+            Self::Relaxation(v) => write!(f, "relaxation must be positive, got {v}"),
+            Self::Damping(v) => write!(f, "damping must be in (0, 1], got {v}"),
+            Self::Gravity(v) => write!(f, "gravity must be finite, got {v}"),
+            Self::TensileK(v) => write!(f, "tensile K must be non-negative, got {v}"),
+            Self::TensileN(v) => write!(f, "tensile N must be at least 1, got {v}"),
+            Self::TensileDq(v) => {
+                write!(f, "tensile reference distance must be in (0, 1), got {v}")
+            }
+            Self::TimeStep(v) => write!(f, "time step must be positive, got {v}"),
+        }
+    }
+}
+
+impl Error for SimParamsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_valid() {
+        assert_eq!(SimParams::builder().build(), Ok(SimParams::default()));
+    }
+
+    #[test]
+    fn rejects_non_positive_radius() {
+        for radius in [0.0, -0.01, Real::NAN, Real::INFINITY] {
+            let err = SimParams::builder().radius(radius).build().unwrap_err();
+            assert!(matches!(err, SimParamsError::Radius(_)), "{err}");
+        }
+    }
+
+    #[test]
+    fn rejects_ratios_that_miss_the_rest_spacing() {
+        for ratio in [0.5, 1.0, 2.0, Real::INFINITY] {
+            let err = SimParams::builder().ratio(ratio).build().unwrap_err();
+            assert!(matches!(err, SimParamsError::Ratio(_)), "{err}");
+        }
+        assert!(SimParams::builder().ratio(2.5).build().is_ok());
+    }
+
+    #[test]
+    fn rejects_zero_solver_iterations() {
+        let err = SimParams::builder().solver_iterations(0).build();
+        assert_eq!(err, Err(SimParamsError::SolverIterations));
+    }
+
+    #[test]
+    fn rejects_non_finite_gravity() {
+        for gravity in [
+            Vector::new(0.0, Real::NAN, 0.0),
+            Vector::new(Real::INFINITY, -9.81, 0.0),
+        ] {
+            let err = SimParams::builder().gravity(gravity).build().unwrap_err();
+            assert!(matches!(err, SimParamsError::Gravity(_)), "{err}");
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use std::f32::consts::FRAC_PI_4;
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::PrimitiveTopology,
+        primitives::Aabb,
+        render_asset::RenderAssetUsages,
+        render_resource::{AsBindGroup, ShaderRef},
+    },
+    window::PrimaryWindow,
+};
+
+use pbf_rs::precision::{to_f32, to_vec3};
+use pbf_rs::simulator::Simulator;
+
+const IMPOSTOR_SHADER: &str = "shaders/particle_impostor.wgsl";
+// radius on screen in pixels down to which a level of detail is used, points below
+// impostors are exact spheres at any size, a low-poly sphere looks the same a few pixels
+// across and keeps the early depth test impostors lose by writing their depth
+const IMPOSTOR_PIXELS: f32 = 4.0;
+const LOW_POLY_PIXELS: f32 = 1.0;
+
+// a camera-facing quad shaded as a sphere, see assets/shaders/particle_impostor.wgsl
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+pub struct ImpostorMaterial {
+    #[uniform(0)]
+    color: LinearRgba,
+}
+
+impl Material for ImpostorMaterial {
+    fn vertex_shader() -> ShaderRef {
+        IMPOSTOR_SHADER.into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        IMPOSTOR_SHADER.into()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum RenderMode {
+    #[default]
+    Spheres, // every particle a detailed sphere mesh
+    LevelOfDetail, // impostors, low-poly spheres or points by their size on screen
+}
+
+#[derive(Resource, Default)]
+pub struct ParticleRender {
+    pub mode: RenderMode,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Detail {
+    Sphere,
+    Impostor,
+    LowPoly,
+    Point,
+}
+
+#[derive(Component)]
+pub struct ParticleDetail {
+    style: usize, // index into ParticleAssets::styles
+    detail: Detail,
+}
+
+// the materials of one particle color
+struct Style {
+    color: [u8; 4],
+    lit: Handle<StandardMaterial>,
+    unlit: Handle<StandardMaterial>,
+    impostor: Handle<ImpostorMaterial>,
+}
+
+// unit size meshes shared by all particles, scaled by the particle radius,
+// and the materials shared by the particles of a color
+#[derive(Resource)]
+pub struct ParticleAssets {
+    sphere: Handle<Mesh>,
+    low_poly: Handle<Mesh>,
+    quad: Handle<Mesh>,
+    point: Handle<Mesh>,
+    styles: Vec<Style>,
+}
+
+impl ParticleAssets {
+    pub fn new(meshes: &mut Assets<Mesh>) -> Self {
+        let point = Mesh::new(
+            PrimitiveTopology::PointList,
+            RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vec![[0.0f32; 3]])
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0f32, 0.0, 1.0]]);
+        Self {
+            sphere: meshes.add(Sphere::new(1.0).mesh().ico(4).unwrap()),
+            low_poly: meshes.add(Sphere::new(1.0).mesh().ico(1).unwrap()),
+            quad: meshes.add(Rectangle::new(2.0, 2.0)),
+            point: meshes.add(point),
+            styles: Vec::new(),
+        }
+    }
+
+    fn style(
+        &mut self,
+        color: Color,
+        materials: &mut Assets<StandardMaterial>,
+        impostors: &mut Assets<ImpostorMaterial>,
+    ) -> usize {
+        let key = color.to_srgba().to_u8_array();
+        if let Some(i) = self.styles.iter().position(|style| style.color == key) {
+            return i;
+        }
+        self.styles.push(Style {
+            color: key,
+            lit: materials.add(StandardMaterial {
+                base_color: color,
+                metallic: 0.2,
+                perceptual_roughness: 0.7,
+                ..default()
+            }),
+            unlit: materials.add(StandardMaterial {
+                base_color: color,
+                unlit: true,
+                ..default()
+            }),
+            impostor: impostors.add(ImpostorMaterial {
+                color: color.into(),
+            }),
+        });
+        self.styles.len() - 1
+    }
+
+    // a detailed sphere at the particle, the detail system takes it from there
+    pub fn bundle(
+        &mut self,
+        simulator: &Simulator,
+        index: usize,
+        materials: &mut Assets<StandardMaterial>,
+        impostors: &mut Assets<ImpostorMaterial>,
+    ) -> impl Bundle {
+        let c = to_vec3(simulator.color[index]);
+        let style = self.style(Color::srgb(c.x, c.y, c.z), materials, impostors);
+        (
+            Mesh3d(self.sphere.clone()),
+            MeshMaterial3d(self.styles[style].lit.clone()),
+            Transform::from_translation(to_vec3(simulator.to_world(simulator.position[index])))
+                .with_scale(Vec3::splat(to_f32(simulator.radius))),
+            // every level of detail stays within the sphere
+            Aabb::from_min_max(Vec3::NEG_ONE, Vec3::ONE),
+            ParticleDetail {
+                style,
+                detail: Detail::Sphere,
+            },
+        )
+    }
+}
+
+// M switches between detailed spheres and levels of detail
+pub fn render_mode_system(keys: Res<ButtonInput<KeyCode>>, mut render: ResMut<ParticleRender>) {
+    if keys.just_pressed(KeyCode::KeyM) {
+        render.mode = match render.mode {
+            RenderMode::Spheres => RenderMode::LevelOfDetail,
+            RenderMode::LevelOfDetail => RenderMode::Spheres,
+        };
+        info!("particle render mode {:?}", render.mode);
+    }
+}
+
+// pick the level of detail of every particle from its radius on screen,
+// and swap its mesh and material when that changes
+pub fn particle_detail_system(
+    commands: ParallelCommands,
+    render: Res<ParticleRender>,
+    assets: Res<ParticleAssets>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Transform, &Projection), With<Camera3d>>,
+    mut query: Query<(Entity, &Transform, &mut ParticleDetail), Without<Camera3d>>,
+) {
+    let (Ok(window), Ok((camera, projection))) = (window.single(), camera.single()) else {
+        return;
+    };
+    let fov = match projection {
+        Projection::Perspective(p) => p.fov,
+        _ => FRAC_PI_4,
+    };
+    // pixels covered by a unit length at unit distance
+    let focal = window.height() / (2.0 * (fov / 2.0).tan());
+
+    query
+        .par_iter_mut()
+        .for_each(|(entity, transform, mut particle)| {
+            let detail = match render.mode {
+                RenderMode::Spheres => Detail::Sphere,
+                RenderMode::LevelOfDetail => {
+                    let distance = transform.translation.distance(camera.translation);
+                    let pixels = focal * transform.scale.x / distance.max(f32::EPSILON);
+                    if pixels >= IMPOSTOR_PIXELS {
+                        Detail::Impostor
+                    } else if pixels >= LOW_POLY_PIXELS {
+                        Detail::LowPoly
+                    } else {
+                        Detail::Point
+                    }
+                }
+            };
+            if detail == particle.detail {
+                return;
+            }
+            particle.detail = detail;
+            let style = &assets.styles[particle.style];
+            commands.command_scope(|mut commands| {
+                let mut entity = commands.entity(entity);
+                let (mesh, material) = match detail {
+                    Detail::Impostor => {
+                        entity.remove::<MeshMaterial3d<StandardMaterial>>().insert((
+                            Mesh3d(assets.quad.clone()),
+                            MeshMaterial3d(style.impostor.clone()),
+                        ));
+                        return;
+                    }
+                    Detail::Sphere => (&assets.sphere, &style.lit),
+                    Detail::LowPoly => (&assets.low_poly, &style.lit),
+                    Detail::Point => (&assets.point, &style.unlit),
+                };
+                entity
+                    .remove::<MeshMaterial3d<ImpostorMaterial>>()
+                    .insert((Mesh3d(mesh.clone()), MeshMaterial3d(material.clone())));
+            });
+        });
+}
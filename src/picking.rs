@@ -0,0 +1,136 @@
+use bevy::{color::palettes::basic::*, prelude::*, window::PrimaryWindow};
+
+use pbf_rs::precision::{Real, from_vec3, to_f32, to_vec3};
+use pbf_rs::simulator::{Grab, GrabMode, Simulator};
+
+// interaction radius and spring strength of the mouse grab
+#[derive(Resource)]
+pub struct PickSettings {
+    pub radius: f32,
+    pub strength: f32,
+}
+
+impl Default for PickSettings {
+    fn default() -> Self {
+        Self {
+            radius: 0.1,
+            strength: 200.0,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct PickSettingsText;
+
+pub fn setup_pick_settings_text(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.spawn((
+        PickSettingsText,
+        Text::new(""),
+        TextFont {
+            font: assets.load("fonts/FiraSans-Bold.ttf"),
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(20.0),
+            bottom: Val::Px(20.0),
+            ..default()
+        },
+    ));
+}
+
+// [ and ] change the grab radius, - and = the grab strength
+pub fn pick_settings_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<PickSettings>,
+    mut text_query: Query<&mut Text, With<PickSettingsText>>,
+) {
+    if keys.just_pressed(KeyCode::BracketLeft) {
+        settings.radius = (settings.radius / 1.25).max(0.02);
+    }
+    if keys.just_pressed(KeyCode::BracketRight) {
+        settings.radius = (settings.radius * 1.25).min(1.0);
+    }
+    if keys.just_pressed(KeyCode::Minus) {
+        settings.strength = (settings.strength / 1.5).max(10.0);
+    }
+    if keys.just_pressed(KeyCode::Equal) {
+        settings.strength = (settings.strength * 1.5).min(10000.0);
+    }
+    if settings.is_changed() {
+        for mut text in &mut text_query {
+            **text = format!(
+                "Grab radius {:.2}  strength {:.0}",
+                settings.radius, settings.strength
+            );
+        }
+    }
+}
+
+// shift + left drag pulls the fluid towards the cursor, shift + right drag pushes it away
+// the grab stays at the depth of the particle hit when the button was pressed
+pub fn pick_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<Camera3d>>,
+    settings: Res<PickSettings>,
+    mut depth: Local<Option<Real>>,
+    mut simulator: ResMut<Simulator>,
+) {
+    let mode = if mouse.pressed(MouseButton::Left) {
+        Some(GrabMode::Attract)
+    } else if mouse.pressed(MouseButton::Right) {
+        Some(GrabMode::Repel)
+    } else {
+        None
+    };
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    let ray = window
+        .single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+        .zip(camera.single().ok())
+        .and_then(|(cursor, (camera, transform))| camera.viewport_to_world(transform, cursor).ok());
+
+    let (Some(mode), Some(ray)) = (mode, ray) else {
+        *depth = None;
+        if simulator.grab().is_some() {
+            simulator.set_grab(None);
+        }
+        return;
+    };
+
+    let origin = from_vec3(ray.origin);
+    let dir = from_vec3(*ray.direction);
+    if depth.is_none() && shift {
+        *depth = simulator.raycast(origin, dir);
+    }
+    let Some(t) = *depth else {
+        return;
+    };
+
+    simulator.set_grab(Some(Grab {
+        center: origin + dir * t,
+        radius: settings.radius as Real,
+        strength: settings.strength as Real,
+        mode,
+    }));
+}
+
+pub fn grab_gizmo_system(mut gizmos: Gizmos, simulator: Res<Simulator>) {
+    if let Some(grab) = simulator.grab() {
+        let color = match grab.mode {
+            GrabMode::Attract => LIME,
+            GrabMode::Repel => RED,
+        };
+        gizmos.sphere(
+            Isometry3d::from_translation(to_vec3(grab.center)),
+            to_f32(grab.radius),
+            color,
+        );
+    }
+}
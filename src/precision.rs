@@ -0,0 +1,81 @@
+// scalar and vector types used by the solver
+// single precision by default, double precision with the `f64` feature
+
+use bevy::math::{Quat, Vec3};
+
+#[cfg(not(feature = "f64"))]
+mod types {
+    pub use bevy::math::vec3 as vector;
+
+    pub type Real = f32;
+    pub type Vector = bevy::math::Vec3;
+    pub type Rotation = bevy::math::Quat;
+    pub type Matrix = bevy::math::Mat3;
+}
+
+#[cfg(feature = "f64")]
+mod types {
+    pub use bevy::math::dvec3 as vector;
+
+    pub type Real = f64;
+    pub type Vector = bevy::math::DVec3;
+    pub type Rotation = bevy::math::DQuat;
+    pub type Matrix = bevy::math::DMat3;
+}
+
+pub use types::*;
+
+// convert a solver vector into the single precision vector used for rendering
+#[cfg(not(feature = "f64"))]
+pub fn to_vec3(v: Vector) -> Vec3 {
+    v
+}
+
+#[cfg(feature = "f64")]
+pub fn to_vec3(v: Vector) -> Vec3 {
+    v.as_vec3()
+}
+
+// convert a solver scalar into the single precision float used for rendering
+#[cfg(not(feature = "f64"))]
+pub fn to_f32(x: Real) -> f32 {
+    x
+}
+
+#[cfg(feature = "f64")]
+pub fn to_f32(x: Real) -> f32 {
+    x as f32
+}
+
+// convert a solver rotation into the single precision quaternion used for rendering
+#[cfg(not(feature = "f64"))]
+pub fn to_quat(q: Rotation) -> Quat {
+    q
+}
+
+#[cfg(feature = "f64")]
+pub fn to_quat(q: Rotation) -> Quat {
+    q.as_quat()
+}
+
+// convert a single precision vector from the viewer into a solver vector
+#[cfg(not(feature = "f64"))]
+pub fn from_vec3(v: Vec3) -> Vector {
+    v
+}
+
+#[cfg(feature = "f64")]
+pub fn from_vec3(v: Vec3) -> Vector {
+    v.as_dvec3()
+}
+
+// convert a single precision quaternion from the viewer into a solver rotation
+#[cfg(not(feature = "f64"))]
+pub fn from_quat(q: Quat) -> Rotation {
+    q
+}
+
+#[cfg(feature = "f64")]
+pub fn from_quat(q: Quat) -> Rotation {
+    q.as_dquat()
+}
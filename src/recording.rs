@@ -0,0 +1,123 @@
+use std::collections::VecDeque;
+use std::mem::size_of;
+
+use crate::checkpoint::{BodyState, Checkpoint, SimState};
+use crate::diffuse::DiffuseParticle;
+use crate::precision::{Real, Rotation, Vector};
+use crate::scene_desc::SceneDesc;
+
+// the latest states of a run of one scene in time order, the oldest are dropped to stay
+// within the budget
+// the scene is kept once, every frame holds only what changes from step to step
+#[derive(Clone, Debug)]
+pub struct Recording {
+    scene: Option<SceneDesc>,
+    frames: VecDeque<SimState>,
+    budget: usize, // bytes
+    bytes: usize,
+}
+
+// memory held by a frame
+fn frame_bytes(frame: &SimState) -> usize {
+    let diffuse = frame.diffuse.as_ref().map_or(0, |d| d.particles.len());
+    size_of::<SimState>()
+        + (frame.position.len() + frame.velocity.len()) * size_of::<Vector>()
+        + frame.rigid_bodies.len() * size_of::<BodyState>()
+        + frame.solids.len() * size_of::<Rotation>()
+        + diffuse * size_of::<DiffuseParticle>()
+}
+
+impl Recording {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            scene: None,
+            frames: VecDeque::new(),
+            budget,
+            bytes: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    // the scene every frame is a state of
+    pub fn scene(&self) -> Option<&SceneDesc> {
+        self.scene.as_ref()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&SimState> {
+        self.frames.get(index)
+    }
+
+    pub fn last(&self) -> Option<&SimState> {
+        self.frames.back()
+    }
+
+    // a frame with the scene, to save it or restore it in another simulator
+    pub fn checkpoint(&self, index: usize) -> Option<Checkpoint> {
+        Some(Checkpoint {
+            scene: self.scene.clone()?,
+            state: self.frames.get(index)?.clone(),
+        })
+    }
+
+    // time span of the recording
+    pub fn start_time(&self) -> Option<Real> {
+        self.frames.front().map(|frame| frame.time)
+    }
+
+    pub fn end_time(&self) -> Option<Real> {
+        self.frames.back().map(|frame| frame.time)
+    }
+
+    // drop every frame and record states of another scene from now on
+    pub fn start(&mut self, scene: SceneDesc) {
+        self.clear();
+        self.scene = Some(scene);
+    }
+
+    // append a frame of the scene the recording started with, later than every frame
+    // already recorded
+    // the newest frame is always kept, even when it alone exceeds the budget
+    pub fn push(&mut self, frame: SimState) {
+        debug_assert!(self.scene.is_some());
+        debug_assert!(self.end_time().is_none_or(|time| time <= frame.time));
+        self.bytes += frame_bytes(&frame);
+        self.frames.push_back(frame);
+        while self.bytes > self.budget && self.frames.len() > 1 {
+            let oldest = self.frames.pop_front().unwrap();
+            self.bytes -= frame_bytes(&oldest);
+        }
+    }
+
+    // the last frame at or before the given time, the first frame before the recording starts
+    pub fn index_at(&self, time: Real) -> Option<usize> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        let after = self.frames.partition_point(|frame| frame.time <= time);
+        Some(after.saturating_sub(1))
+    }
+
+    // keep the first len frames, to record a different future from the last of them
+    pub fn truncate(&mut self, len: usize) {
+        for frame in self.frames.drain(len.min(self.frames.len())..) {
+            self.bytes -= frame_bytes(&frame);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.scene = None;
+        self.frames.clear();
+        self.bytes = 0;
+    }
+}
@@ -0,0 +1,270 @@
+use bevy::math::EulerRot;
+use serde::{Deserialize, Serialize};
+
+use crate::boundary::{Shape, Surface};
+use crate::motion::Pose;
+use crate::precision::{Real, Rotation, Vector, vector};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BodyShape {
+    Box { half_extents: Vector },
+    Sphere { radius: Real },
+}
+
+impl BodyShape {
+    // every extent positive and finite, anything else has no volume
+    pub fn is_solid(&self) -> bool {
+        match self {
+            BodyShape::Box { half_extents } => {
+                half_extents.is_finite() && half_extents.cmpgt(Vector::ZERO).all()
+            }
+            BodyShape::Sphere { radius } => radius.is_finite() && *radius > 0.0,
+        }
+    }
+
+    pub fn volume(&self) -> Real {
+        match self {
+            BodyShape::Box { half_extents } => {
+                8.0 * half_extents.x * half_extents.y * half_extents.z
+            }
+            BodyShape::Sphere { radius } => {
+                4.0 / 3.0 * std::f64::consts::PI as Real * radius * radius * radius
+            }
+        }
+    }
+
+    // principal moments of inertia of the solid shape with unit mass
+    fn unit_inertia(&self) -> Vector {
+        match self {
+            BodyShape::Box { half_extents: e } => {
+                let e2 = *e * *e;
+                vector(e2.y + e2.z, e2.x + e2.z, e2.x + e2.y) / 3.0
+            }
+            BodyShape::Sphere { radius } => Vector::splat(0.4 * radius * radius),
+        }
+    }
+
+    pub fn shape(&self) -> Shape {
+        match self {
+            BodyShape::Box { half_extents } => Shape::Box {
+                half_extents: *half_extents,
+            },
+            BodyShape::Sphere { radius } => Shape::Sphere { radius: *radius },
+        }
+    }
+
+    // points of the body frame that can touch a flat wall with the given outward normal
+    fn support_points(&self, normal: Vector) -> Vec<Vector> {
+        match self {
+            BodyShape::Box { half_extents: e } => (0..8)
+                .map(|k| {
+                    let sign = |bit: usize| if k & bit == 0 { -1.0 } else { 1.0 };
+                    vector(sign(1) * e.x, sign(2) * e.y, sign(4) * e.z)
+                })
+                .collect(),
+            BodyShape::Sphere { radius } => vec![normal * *radius],
+        }
+    }
+}
+
+// a floating or sinking object, coupled both ways with the particles
+// positions and velocities are given in the tank frame
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "RigidBodyDesc", into = "RigidBodyDesc")]
+pub struct RigidBody {
+    pub shape: BodyShape,
+    pub density: Real, // kg/m^3, water is 1000
+    pub surface: Surface,
+    mass: Real,
+    inertia: Vector,      // principal moments in the body frame
+    pub position: Vector, // center of mass
+    pub rotation: Rotation,
+    pub velocity: Vector,
+    pub angular_velocity: Vector,
+    // momentum times time step handed over by the particles during the current step
+    linear_exchange: Vector,
+    angular_exchange: Vector,
+}
+
+// the serialized part of a rigid body, rotation is given as XYZ euler angles
+#[derive(Serialize, Deserialize)]
+struct RigidBodyDesc {
+    shape: BodyShape,
+    density: Real,
+    position: Vector,
+    #[serde(default)]
+    rotation: Vector,
+    #[serde(default)]
+    velocity: Vector,
+    #[serde(default)]
+    surface: Surface,
+}
+
+impl From<RigidBodyDesc> for RigidBody {
+    fn from(desc: RigidBodyDesc) -> Self {
+        let r = desc.rotation;
+        let mut body = RigidBody::new(
+            desc.shape,
+            desc.density,
+            desc.position,
+            Rotation::from_euler(EulerRot::XYZ, r.x, r.y, r.z),
+        );
+        body.velocity = desc.velocity;
+        body.surface = desc.surface;
+        body
+    }
+}
+
+impl From<RigidBody> for RigidBodyDesc {
+    fn from(body: RigidBody) -> Self {
+        let (x, y, z) = body.rotation.to_euler(EulerRot::XYZ);
+        Self {
+            shape: body.shape,
+            density: body.density,
+            position: body.position,
+            rotation: vector(x, y, z),
+            velocity: body.velocity,
+            surface: body.surface,
+        }
+    }
+}
+
+impl RigidBody {
+    pub fn new(shape: BodyShape, density: Real, position: Vector, rotation: Rotation) -> Self {
+        let mass = density * shape.volume();
+        Self {
+            inertia: mass * shape.unit_inertia(),
+            shape,
+            density,
+            surface: Surface::default(),
+            mass,
+            position,
+            rotation,
+            velocity: Vector::ZERO,
+            angular_velocity: Vector::ZERO,
+            linear_exchange: Vector::ZERO,
+            angular_exchange: Vector::ZERO,
+        }
+    }
+
+    pub fn mass(&self) -> Real {
+        self.mass
+    }
+
+    pub fn pose(&self) -> Pose {
+        Pose {
+            translation: self.position,
+            rotation: self.rotation,
+        }
+    }
+
+    // outward normal of the surface point closest to p
+    pub fn normal_at(&self, p: Vector) -> Vector {
+        let q = self.rotation.inverse() * (p - self.position);
+        let n = match &self.shape {
+            BodyShape::Box { half_extents: e } => {
+                let outside = q - q.clamp(-*e, *e);
+                if outside != Vector::ZERO {
+                    outside.normalize()
+                } else {
+                    // inside, towards the closest face
+                    let depth = *e - q.abs();
+                    let axis = if depth.x <= depth.y && depth.x <= depth.z {
+                        Vector::X
+                    } else if depth.y <= depth.z {
+                        Vector::Y
+                    } else {
+                        Vector::Z
+                    };
+                    axis * q.signum()
+                }
+            }
+            BodyShape::Sphere { .. } => q.normalize_or(Vector::Y),
+        };
+        self.rotation * n
+    }
+
+    pub fn velocity_at(&self, p: Vector) -> Vector {
+        self.velocity + self.angular_velocity.cross(p - self.position)
+    }
+
+    // turn the whole body about the tank center, used when the tank frame rotates
+    pub fn rotate_frame(&mut self, delta: Rotation) {
+        self.position = delta * self.position;
+        self.rotation = (delta * self.rotation).normalize();
+        self.velocity = delta * self.velocity;
+        self.angular_velocity = delta * self.angular_velocity;
+    }
+
+    fn apply_inverse_inertia(&self, v: Vector) -> Vector {
+        self.rotation * ((self.rotation.inverse() * v) / self.inertia)
+    }
+
+    // a particle pushed by the body moved by dx, the body takes the opposite momentum
+    pub fn exchange(&mut self, point: Vector, particle_mass: Real, dx: Vector) {
+        let momentum = -particle_mass * dx;
+        self.linear_exchange += momentum;
+        self.angular_exchange += (point - self.position).cross(momentum);
+    }
+
+    // advance the pose with the velocity of the last step plus gravity
+    pub fn integrate(&mut self, dt: Real, gravity: Vector) {
+        self.velocity += gravity * dt;
+        self.position += self.velocity * dt;
+        let w = self.angular_velocity * dt;
+        let spin = Rotation::from_xyzw(w.x, w.y, w.z, 0.0) * self.rotation;
+        self.rotation = (self.rotation + spin * 0.5).normalize();
+    }
+
+    // turn the momentum handed over by the particles into velocity
+    pub fn apply_exchange(&mut self, dt: Real) {
+        self.velocity += self.linear_exchange / (self.mass * dt);
+        self.angular_velocity += self.apply_inverse_inertia(self.angular_exchange / dt);
+        self.linear_exchange = Vector::ZERO;
+        self.angular_exchange = Vector::ZERO;
+    }
+
+    // keep the body inside the tank walls with an impulse at every touching point
+    pub fn collide_tank(&mut self, half: Vector) {
+        for axis in 0..3 {
+            for sign in [-1.0, 1.0] {
+                let mut outward = Vector::ZERO;
+                outward[axis] = sign;
+                let normal = -outward;
+                for local in self.shape.support_points(self.rotation.inverse() * outward) {
+                    let p = self.position + self.rotation * local;
+                    let depth = p[axis] * sign - half[axis];
+                    if depth <= 0.0 {
+                        continue;
+                    }
+                    self.position += normal * depth;
+                    self.apply_contact_impulse(p + normal * depth, normal);
+                }
+            }
+        }
+    }
+
+    fn apply_contact_impulse(&mut self, p: Vector, normal: Vector) {
+        let r = p - self.position;
+        let v = self.velocity_at(p);
+        let vn = v.dot(normal);
+        if vn >= 0.0 {
+            return;
+        }
+        let k = |dir: Vector| {
+            1.0 / self.mass + dir.dot(self.apply_inverse_inertia(r.cross(dir)).cross(r))
+        };
+        let jn = -(1.0 + self.surface.restitution) * vn / k(normal);
+        let mut impulse = jn * normal;
+
+        let vt = v - vn * normal;
+        let speed = vt.length();
+        if speed > 1e-9 {
+            let t = vt / speed;
+            let jt = (speed / k(t)).min(self.surface.friction * jn);
+            impulse -= jt * t;
+        }
+        self.velocity += impulse / self.mass;
+        self.angular_velocity += self.apply_inverse_inertia(r.cross(impulse));
+    }
+}
@@ -0,0 +1,68 @@
+use bevy::prelude::*;
+
+use pbf_rs::precision::{to_f32, to_quat, to_vec3};
+use pbf_rs::rigid_body::BodyShape;
+use pbf_rs::simulator::Simulator;
+
+#[derive(Component)]
+pub struct RigidBodyMesh(usize);
+
+// spawn a mesh for every rigid body, again whenever a new scene brings other bodies
+pub fn rigid_body_spawn_system(
+    mut commands: Commands,
+    simulator: Res<Simulator>,
+    query: Query<Entity, With<RigidBodyMesh>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut spawned: Local<Vec<BodyShape>>,
+) {
+    let shapes: Vec<BodyShape> = simulator
+        .rigid_bodies
+        .iter()
+        .map(|b| b.shape.clone())
+        .collect();
+    if *spawned == shapes {
+        return;
+    }
+    for entity in &query {
+        commands.entity(entity).despawn();
+    }
+
+    let material = materials.add(StandardMaterial {
+        base_color: Color::srgb(0.55, 0.35, 0.2),
+        perceptual_roughness: 0.8,
+        ..default()
+    });
+    for (i, shape) in shapes.iter().enumerate() {
+        let mesh = match shape {
+            BodyShape::Box { half_extents } => {
+                meshes.add(Cuboid::from_size(to_vec3(2.0 * *half_extents)))
+            }
+            BodyShape::Sphere { radius } => {
+                meshes.add(Sphere::new(to_f32(*radius)).mesh().ico(4).unwrap())
+            }
+        };
+        commands.spawn((
+            Mesh3d(mesh),
+            MeshMaterial3d(material.clone()),
+            Transform::default(),
+            RigidBodyMesh(i),
+        ));
+    }
+    *spawned = shapes;
+}
+
+// copy the body poses into the world frame every frame
+pub fn rigid_body_sync_system(
+    simulator: Res<Simulator>,
+    mut query: Query<(&RigidBodyMesh, &mut Transform)>,
+) {
+    let tank_rotation = simulator.tank_rotation();
+    for (body, mut transform) in &mut query {
+        let Some(body) = simulator.rigid_bodies.get(body.0) else {
+            continue;
+        };
+        transform.translation = to_vec3(simulator.to_world(body.position));
+        transform.rotation = to_quat(tank_rotation * body.rotation);
+    }
+}
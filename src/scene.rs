@@ -1,9 +1,18 @@
-use bevy::{color::palettes::basic::*, input::mouse::MouseWheel, prelude::*, render::render_asset::RenderAssetUsages};
+use bevy::{color::palettes::basic::*, prelude::*, render::render_asset::RenderAssetUsages};
 
-use crate::simulator::Simulator;
+use pbf_rs::boundary::box_edges;
+use pbf_rs::precision::{to_quat, to_vec3};
+use pbf_rs::simulator::Simulator;
 
-const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
-const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
+use crate::cache_playback::CachePlayback;
+use crate::capture::Capture;
+use crate::library::SceneLibrary;
+use crate::particles::{ImpostorMaterial, ParticleAssets};
+use crate::stepping::Stepper;
+use crate::timeline::Timeline;
+
+pub const NORMAL_BUTTON: Color = Color::srgb(0.15, 0.15, 0.15);
+pub const HOVERED_BUTTON: Color = Color::srgb(0.25, 0.25, 0.25);
 
 #[derive(Resource, Default)]
 pub struct SimRunning(pub bool);
@@ -128,6 +137,7 @@ fn button(asset_server: &AssetServer) -> impl Bundle {
     )
 }
 
+#[allow(clippy::type_complexity)]
 pub fn reset_sim_button_system(
     mut interaction_query: Query<
         (
@@ -139,13 +149,31 @@ pub fn reset_sim_button_system(
         (Changed<Interaction>, With<ResetSimButton>),
     >,
     mut simulator: ResMut<Simulator>,
+    commands: ParallelCommands,
+    query: Query<Entity, With<Particle>>,
+    assets: ResMut<ParticleAssets>,
+    materials: ResMut<Assets<StandardMaterial>>,
+    impostors: ResMut<Assets<ImpostorMaterial>>,
 ) {
     for (interaction, mut color, mut border_color, _) in &mut interaction_query {
         match *interaction {
             Interaction::Pressed => {
-                simulator.reset_system();
                 *color = GREEN.into();
                 border_color.0 = GREEN.into();
+                let radius = simulator.radius;
+                simulator.reset_system();
+                // a new radius changes the particle count and size
+                if simulator.radius != radius {
+                    rebuild_particles_system(
+                        commands,
+                        query,
+                        simulator.into(),
+                        assets,
+                        materials,
+                        impostors,
+                    );
+                    return;
+                }
             }
             Interaction::Hovered => {
                 *color = HOVERED_BUTTON.into();
@@ -159,29 +187,20 @@ pub fn reset_sim_button_system(
     }
 }
 
+#[allow(clippy::type_complexity)]
 pub fn pause_resume_button_system(
     mut interaction_query: Query<
-        (
-            &Interaction,
-            &mut BackgroundColor,
-            &mut BorderColor,
-            &Children,
-        ),
+        (&Interaction, &mut BackgroundColor, &mut BorderColor),
         (Changed<Interaction>, With<PauseResumeButton>),
     >,
+    button_query: Query<&Children, With<PauseResumeButton>>,
     mut text_query: Query<&mut Text>,
     mut sim_running: ResMut<SimRunning>,
 ) {
-    for (interaction, mut color, mut border_color, children) in &mut interaction_query {
-        let mut text = text_query.get_mut(children[0]).unwrap();
+    for (interaction, mut color, mut border_color) in &mut interaction_query {
         match *interaction {
             Interaction::Pressed => {
                 sim_running.0 = !sim_running.0;
-                **text = if sim_running.0 {
-                    "Stop Simulation".to_string()
-                } else {
-                    "Continue".to_string()
-                };
                 if sim_running.0 {
                     *color = GREEN.into();
                     border_color.0 = GREEN.into();
@@ -200,8 +219,23 @@ pub fn pause_resume_button_system(
             }
         }
     }
+
+    // the simulation is also stopped by stepping it
+    if sim_running.is_changed() {
+        let label = if sim_running.0 {
+            "Stop Simulation"
+        } else {
+            "Continue"
+        };
+        for children in &button_query {
+            if let Ok(mut text) = text_query.get_mut(children[0]) {
+                **text = label.to_string();
+            }
+        }
+    }
 }
 
+#[allow(clippy::type_complexity)]
 pub fn switch_scene_button_system(
     mut interaction_query: Query<
         (
@@ -212,13 +246,13 @@ pub fn switch_scene_button_system(
         ),
         (Changed<Interaction>, With<SwitchSceneButton>),
     >,
+    mut library: ResMut<SceneLibrary>,
     mut simulator: ResMut<Simulator>,
 ) {
     for (interaction, mut color, mut border_color, _) in &mut interaction_query {
         match *interaction {
             Interaction::Pressed => {
-                simulator.scene_id = (simulator.scene_id + 1) % 2;
-                simulator.scene_changed = true;
+                simulator.set_scene(library.next().clone());
                 *color = GREEN.into();
                 border_color.0 = GREEN.into();
             }
@@ -234,32 +268,36 @@ pub fn switch_scene_button_system(
     }
 }
 
+// line list vertices and indices of the tank and every wall and collider
+fn boundary_lines(simulator: &Simulator) -> (Vec<Vec3>, Vec<u32>) {
+    let mut positions = Vec::new();
+    let segments = box_edges(simulator.tank / 2.0).into_iter().chain(
+        simulator
+            .boundaries
+            .iter()
+            .flat_map(|b| b.wireframe(simulator.tank)),
+    );
+    for [a, b] in segments {
+        positions.push(to_vec3(a));
+        positions.push(to_vec3(b));
+    }
+    let indices = (0..positions.len() as u32).collect();
+    (positions, indices)
+}
+
 pub fn update_boundary(
     simulator: Res<Simulator>,
     mut meshes: ResMut<Assets<Mesh>>,
-    query: Query<&Mesh3d, With<Boundary>>,
+    mut query: Query<(&Mesh3d, &mut Transform), With<Boundary>>,
 ) {
-    let half_size = simulator.tank / 2.0;
-    let vertices = [
-        [-half_size.x, -half_size.y, -half_size.z],
-        [half_size.x, -half_size.y, -half_size.z],
-        [half_size.x, half_size.y, -half_size.z],
-        [-half_size.x, half_size.y, -half_size.z],
-        [-half_size.x, -half_size.y, half_size.z],
-        [half_size.x, -half_size.y, half_size.z],
-        [half_size.x, half_size.y, half_size.z],
-        [-half_size.x, half_size.y, half_size.z],
-        [half_size.x * simulator.slide_pos, -half_size.y, half_size.z],
-        [half_size.x * simulator.slide_pos, -half_size.y, -half_size.z],
-        [half_size.x * simulator.slide_pos, half_size.y, -half_size.z],
-        [half_size.x * simulator.slide_pos, half_size.y, half_size.z],
-    ];
-
-    let positions: Vec<Vec3> = vertices.iter().map(|&v| Vec3::from_array(v)).collect();
+    let (positions, indices) = boundary_lines(&simulator);
 
-    for mesh_handle in &query {
+    for (mesh_handle, mut transform) in &mut query {
+        // the lines are built in the tank frame
+        transform.rotation = to_quat(simulator.tank_rotation());
         if let Some(mesh) = meshes.get_mut(mesh_handle) {
             mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions.clone());
+            mesh.insert_indices(bevy::render::mesh::Indices::U32(indices.clone()));
         }
     }
 }
@@ -268,6 +306,7 @@ pub fn setup(
     commands: ParallelCommands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut impostors: ResMut<Assets<ImpostorMaterial>>,
     assets: Res<AssetServer>,
     mut simulator: ResMut<Simulator>,
 ) {
@@ -281,27 +320,9 @@ pub fn setup(
         RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
     );
 
-    let indices: [[u32; 2]; 16] = [
-        [0, 1],
-        [1, 2],
-        [2, 3],
-        [3, 0],
-        [4, 5],
-        [5, 6],
-        [6, 7],
-        [7, 4],
-        [0, 4],
-        [1, 5],
-        [2, 6],
-        [3, 7],
-        [8, 9],
-        [9, 10],
-        [10, 11],
-        [11, 8],
-    ];
-
-    let flat_indices: Vec<u32> = indices.iter().flat_map(|&[a, b]| [a, b]).collect();
-    boundary.insert_indices(bevy::render::mesh::Indices::U32(flat_indices));
+    let (positions, indices) = boundary_lines(&simulator);
+    boundary.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    boundary.insert_indices(bevy::render::mesh::Indices::U32(indices));
 
     let boundary_material = materials.add(StandardMaterial {
         base_color: Color::WHITE,
@@ -317,21 +338,16 @@ pub fn setup(
         ));
     });
 
-    for (i, pos) in simulator.position.iter().enumerate() {
+    let mut particle_assets = ParticleAssets::new(&mut meshes);
+    for i in 0..simulator.num_sphere {
+        let particle = particle_assets.bundle(&simulator, i, &mut materials, &mut impostors);
         commands.command_scope(|mut commands| {
-            commands.spawn((
-                Mesh3d(meshes.add(Sphere::new(simulator.radius).mesh().ico(4).unwrap())),
-                MeshMaterial3d(materials.add(StandardMaterial {
-                    base_color: Color::srgb(0.0, 30.0 / 255.0, 1.0),
-                    metallic: 0.2,
-                    perceptual_roughness: 0.7,
-                    ..default()
-                })),
-                Transform::from_xyz(pos.x, pos.y, pos.z),
-                Particle(i),
-            ));
+            commands.spawn((particle, Particle(i)));
         });
     }
+    commands.command_scope(|mut commands| {
+        commands.insert_resource(particle_assets);
+    });
 
     commands.command_scope(|mut commands| {
         commands.spawn((
@@ -352,64 +368,33 @@ pub fn setup(
     });
 }
 
-pub fn camera_control_system(
-    mut query: Query<&mut Transform, With<Camera3d>>,
-    input: Res<ButtonInput<KeyCode>>,
-    mut mouse_wheel_events: EventReader<MouseWheel>,
-    time: Res<Time>,
-    mut pitch: Local<f32>,
-    mut yaw: Local<f32>,
-    mut distance: Local<f32>,
-) {
-    if *distance == 0.0 {
-        *distance = 3.0;
-    }
-    let mut transform = query.single_mut().unwrap();
-    let rotation_speed = 1.0;
-    let pitch_speed = 1.0;
-
-    for ev in mouse_wheel_events.read() {
-        *distance -= ev.y * 1.0;
-        *distance = distance.clamp(2.0, 50.0);
-    }
-
-    if input.pressed(KeyCode::ArrowLeft) {
-        *yaw += rotation_speed * time.delta_secs();
-    }
-    if input.pressed(KeyCode::ArrowRight) {
-        *yaw -= rotation_speed * time.delta_secs();
-    }
-
-    if input.pressed(KeyCode::ArrowUp) {
-        *pitch += pitch_speed * time.delta_secs();
-    }
-    if input.pressed(KeyCode::ArrowDown) {
-        *pitch -= pitch_speed * time.delta_secs();
-    }
-
-    *pitch = pitch.clamp(
-        -std::f32::consts::FRAC_PI_2 + 0.1,
-        std::f32::consts::FRAC_PI_2 - 0.1,
-    );
-
-    let rotation = Quat::from_rotation_y(*yaw) * Quat::from_rotation_x(*pitch);
-    let target = Vec3::new(0.0, 0.0, 0.0);
-    let offset = rotation * Vec3::new(0.0, 0.0, *distance);
-    transform.translation = target + offset;
-    transform.look_at(target, Vec3::Y);
-}
-
 pub fn simulation_step(
     mut simulator: ResMut<Simulator>,
     mut query: Query<(&Particle, &mut Transform)>,
     sim_running: Res<SimRunning>,
+    mut capture: ResMut<Capture>,
+    timeline: Res<Timeline>,
+    cache: Option<Res<CachePlayback>>,
+    mut stepper: ResMut<Stepper>,
 ) {
-    if sim_running.0 {
-        simulator.simulate_timestep(1.0 / 200.0);
+    // a recorded or cached frame is shown instead
+    if sim_running.0 && !timeline.is_playing_back() && cache.is_none() {
+        // a step run phase by phase is finished first
+        stepper.finish(&mut simulator);
+        let dt = simulator.params().time_step();
+        match capture.target_time(simulator.time) {
+            // a recording steps to the time of its next frame, however long that takes
+            Some(target) => {
+                while simulator.time + 0.5 * dt < target {
+                    simulator.simulate_timestep(dt);
+                }
+            }
+            None => simulator.simulate_timestep(dt),
+        }
     }
 
     query.par_iter_mut().for_each(|(particle, mut transform)| {
-        transform.translation = simulator.position[particle.0];
+        transform.translation = to_vec3(simulator.to_world(simulator.position[particle.0]));
     });
 }
 
@@ -417,13 +402,21 @@ pub fn scene_refresh_system(
     mut simulator: ResMut<Simulator>,
     commands: ParallelCommands,
     query: Query<Entity, With<Particle>>,
-    meshes: ResMut<Assets<Mesh>>,
+    assets: ResMut<ParticleAssets>,
     materials: ResMut<Assets<StandardMaterial>>,
+    impostors: ResMut<Assets<ImpostorMaterial>>,
 ) {
     if simulator.scene_changed {
         simulator.reset_system();
         simulator.scene_changed = false;
-        rebuild_particles_system(commands, query, simulator.into(), meshes, materials);
+        rebuild_particles_system(
+            commands,
+            query,
+            simulator.into(),
+            assets,
+            materials,
+            impostors,
+        );
     }
 }
 
@@ -431,8 +424,9 @@ fn rebuild_particles_system(
     commands: ParallelCommands,
     query: Query<Entity, With<Particle>>,
     simulator: Res<Simulator>,
-    mut meshes: ResMut<Assets<Mesh>>,
+    mut assets: ResMut<ParticleAssets>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut impostors: ResMut<Assets<ImpostorMaterial>>,
 ) {
     query.par_iter().for_each(|entity| {
         commands.command_scope(|mut commands| {
@@ -440,19 +434,10 @@ fn rebuild_particles_system(
         })
     });
 
-    for (i, pos) in simulator.position.iter().enumerate() {
+    for i in 0..simulator.num_sphere {
+        let particle = assets.bundle(&simulator, i, &mut materials, &mut impostors);
         commands.command_scope(|mut commands| {
-            commands.spawn((
-                Mesh3d(meshes.add(Sphere::new(simulator.radius).mesh().ico(4).unwrap())),
-                MeshMaterial3d(materials.add(StandardMaterial {
-                    base_color: Color::srgb(0.0, 30.0 / 255.0, 1.0),
-                    metallic: 0.2,
-                    perceptual_roughness: 0.7,
-                    ..default()
-                })),
-                Transform::from_xyz(pos.x, pos.y, pos.z),
-                Particle(i),
-            ));
+            commands.spawn((particle, Particle(i)));
         });
     }
 }
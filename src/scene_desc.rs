@@ -0,0 +1,312 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use bevy::math::BVec3;
+use serde::{Deserialize, Serialize};
+
+use crate::boundary::{Boundary, Shape, Surface};
+use crate::diffuse::DiffuseParams;
+use crate::force_field::ForceField;
+use crate::material::Material;
+use crate::motion::{Interpolation, Keyframe, Motion, Track};
+use crate::precision::{Real, Vector, vector};
+use crate::rigid_body::RigidBody;
+
+// a box of particles, size is relative to the tank and offset places it between
+// the low (0) and high (1) end of the tank on every axis
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FluidBlock {
+    pub size: Vector,
+    pub offset: Vector,
+    #[serde(default)]
+    pub material: Material,
+}
+
+// a box of particles held together by shape matching, placed in the tank frame
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SolidBlock {
+    pub center: Vector,
+    pub half_extents: Vector,
+    pub stiffness: Real, // 1 is rigid, lower values give a softer body
+    #[serde(default = "SolidBlock::default_friction")]
+    pub friction: Real,
+}
+
+impl SolidBlock {
+    fn default_friction() -> Real {
+        0.5
+    }
+}
+
+// everything needed to set up a simulation, can be loaded from a RON scene file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SceneDesc {
+    pub name: String,
+    pub tank: Vector, // tank size
+    #[serde(default)]
+    pub tank_surface: Surface,
+    #[serde(default)]
+    pub periodic: BVec3, // axes on which particles leaving the tank come back on the other side
+    #[serde(default)]
+    pub blocks: Vec<FluidBlock>,
+    #[serde(default)]
+    pub solids: Vec<SolidBlock>,
+    #[serde(default)]
+    pub rigid_bodies: Vec<RigidBody>,
+    #[serde(default)]
+    pub boundaries: Vec<Boundary>,
+    #[serde(default)]
+    pub force_fields: Vec<ForceField>,
+    #[serde(default)]
+    pub diffuse: Option<DiffuseParams>, // spray, foam and bubbles
+}
+
+impl Default for SceneDesc {
+    fn default() -> Self {
+        Self::falling_block()
+    }
+}
+
+impl SceneDesc {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let text = fs::read_to_string(path).map_err(SceneError::Io)?;
+        Self::parse(&text)
+    }
+
+    fn parse(text: &str) -> Result<Self, SceneError> {
+        let scene: Self = ron::from_str(text).map_err(SceneError::Parse)?;
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    // values the solver cannot run with, e.g. a body without mass would turn every
+    // velocity it touches into NaN
+    fn validate(&self) -> Result<(), SceneError> {
+        for (index, body) in self.rigid_bodies.iter().enumerate() {
+            if !(body.shape.is_solid() && body.density > 0.0 && body.density.is_finite()) {
+                return Err(SceneError::RigidBody(index));
+            }
+        }
+        if let Some(range) = self.diffuse.as_ref().and_then(DiffuseParams::invalid_range) {
+            return Err(SceneError::Diffuse(range));
+        }
+        Ok(())
+    }
+
+    // the scenes shipped with the viewer
+    pub fn builtin() -> Vec<SceneDesc> {
+        vec![Self::falling_block(), Self::wave_maker(), Self::paddle()]
+    }
+
+    pub fn falling_block() -> Self {
+        Self {
+            name: "Falling Block".to_string(),
+            tank: vector(1.0, 2.0, 1.0),
+            tank_surface: Surface::default(),
+            periodic: BVec3::FALSE,
+            blocks: vec![FluidBlock {
+                size: vector(0.8, 0.8, 0.3),
+                offset: vector(0.5, 1.0, 0.7),
+                material: Material::Fluid,
+            }],
+            solids: Vec::new(),
+            rigid_bodies: Vec::new(),
+            boundaries: Vec::new(),
+            force_fields: Vec::new(),
+            diffuse: Some(DiffuseParams::default()),
+        }
+    }
+
+    pub fn wave_maker() -> Self {
+        let tank = vector(2.0, 1.0, 0.5);
+        let half = 0.5 * tank;
+        // a wall sliding between the right end and the middle of the tank
+        let slide = Track::Keyframes {
+            keys: vec![
+                Keyframe::new(0.0, vector(half.x, 0.0, 0.0)),
+                Keyframe::new(0.5, vector(0.5 * half.x, 0.0, 0.0)),
+                Keyframe::new(1.0, vector(half.x, 0.0, 0.0)),
+            ],
+            interpolation: Interpolation::Linear,
+            looping: true,
+        };
+        Self {
+            name: "Wave Maker".to_string(),
+            tank,
+            tank_surface: Surface::default(),
+            periodic: BVec3::FALSE,
+            blocks: vec![FluidBlock {
+                size: vector(0.4, 0.6, 1.0),
+                offset: vector(0.0, 0.0, 0.5),
+                material: Material::Fluid,
+            }],
+            solids: Vec::new(),
+            rigid_bodies: Vec::new(),
+            boundaries: vec![Boundary::new(
+                Shape::Plane {
+                    normal: vector(-1.0, 0.0, 0.0),
+                },
+                Motion {
+                    translation: slide,
+                    rotation: Track::default(),
+                },
+            )],
+            force_fields: Vec::new(),
+            diffuse: Some(DiffuseParams::default()),
+        }
+    }
+
+    pub fn paddle() -> Self {
+        let tank = vector(1.2, 0.8, 0.6);
+        let half = 0.5 * tank;
+        // a swinging paddle and a ball dipping into the water along a spline
+        let paddle = Motion {
+            translation: Track::Constant(vector(-0.25 * half.x, -0.3 * half.y, 0.0)),
+            rotation: Track::Sinusoid {
+                offset: Vector::ZERO,
+                amplitude: vector(0.0, 1.2, 0.0),
+                frequency: 0.4,
+                phase: 0.0,
+            },
+        };
+        let ball = Motion {
+            translation: Track::Keyframes {
+                keys: vec![
+                    Keyframe::new(0.0, vector(0.5 * half.x, 0.4 * half.y, 0.0)),
+                    Keyframe::new(1.0, vector(0.6 * half.x, -0.4 * half.y, 0.5 * half.z)),
+                    Keyframe::new(2.0, vector(0.2 * half.x, 0.0, 0.0)),
+                    Keyframe::new(3.0, vector(0.6 * half.x, -0.4 * half.y, -0.5 * half.z)),
+                    Keyframe::new(4.0, vector(0.5 * half.x, 0.4 * half.y, 0.0)),
+                ],
+                interpolation: Interpolation::CatmullRom,
+                looping: true,
+            },
+            rotation: Track::default(),
+        };
+        Self {
+            name: "Paddle".to_string(),
+            tank,
+            tank_surface: Surface::default(),
+            periodic: BVec3::FALSE,
+            blocks: vec![FluidBlock {
+                size: vector(1.0, 0.4, 1.0),
+                offset: Vector::ZERO,
+                material: Material::Fluid,
+            }],
+            solids: Vec::new(),
+            rigid_bodies: Vec::new(),
+            boundaries: vec![
+                Boundary::new(
+                    Shape::Box {
+                        half_extents: vector(0.03, 0.5 * half.y, 0.6 * half.z),
+                    },
+                    paddle,
+                ),
+                Boundary::new(Shape::Sphere { radius: 0.1 }, ball),
+            ],
+            force_fields: Vec::new(),
+            diffuse: Some(DiffuseParams::default()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    RigidBody(usize), // index of a rigid body without a positive density and size
+    Diffuse(&'static str), // diffuse potential range without min < max
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to read scene file: {err}"),
+            Self::Parse(err) => write!(f, "failed to parse scene file: {err}"),
+            Self::RigidBody(index) => write!(
+                f,
+                "rigid body {index} needs a positive density and size to have a mass"
+            ),
+            Self::Diffuse(range) => write!(f, "diffuse {range} range must have min < max"),
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Parse(err) => Some(err),
+            Self::RigidBody(_) | Self::Diffuse(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scene_with_body(body: &str) -> String {
+        format!("(name: \"Body\", tank: (1.0, 1.0, 1.0), rigid_bodies: [{body}])")
+    }
+
+    #[test]
+    fn shipped_scenes_are_valid() {
+        for scene in SceneDesc::builtin() {
+            assert!(scene.validate().is_ok(), "{}", scene.name);
+        }
+        for entry in fs::read_dir("assets/scenes").unwrap() {
+            let path = entry.unwrap().path();
+            if let Err(err) = SceneDesc::load(&path) {
+                panic!("{}: {err}", path.display());
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_rigid_bodies_without_mass() {
+        let ball = |density: &str, radius: &str| {
+            scene_with_body(&format!(
+                "(shape: Sphere(radius: {radius}), density: {density}, position: (0.0, 0.0, 0.0))"
+            ))
+        };
+        assert!(SceneDesc::parse(&ball("500.0", "0.1")).is_ok());
+        for (density, radius) in [
+            ("0.0", "0.1"),
+            ("-500.0", "0.1"),
+            ("inf", "0.1"),
+            ("500.0", "0.0"),
+        ] {
+            let err = SceneDesc::parse(&ball(density, radius)).unwrap_err();
+            assert!(matches!(err, SceneError::RigidBody(0)), "{err}");
+        }
+        // two negative extents make a positive volume, but not a box
+        let flipped = scene_with_body(
+            "(shape: Box(half_extents: (-0.1, -0.1, 0.1)), density: 500.0, position: (0.0, 0.0, 0.0))",
+        );
+        let err = SceneDesc::parse(&flipped).unwrap_err();
+        assert!(matches!(err, SceneError::RigidBody(0)), "{err}");
+    }
+
+    #[test]
+    fn rejects_empty_diffuse_ranges() {
+        let scene = |diffuse: &str| {
+            format!("(name: \"Diffuse\", tank: (1.0, 1.0, 1.0), diffuse: Some(({diffuse})))")
+        };
+        assert!(SceneDesc::parse(&scene("wave_crest: (1.0, 2.0)")).is_ok());
+        for (diffuse, range) in [
+            ("trapped_air: (2.0, 2.0)", "trapped_air"),
+            ("wave_crest: (4.0, 1.0)", "wave_crest"),
+            ("kinetic_energy: (0.5, inf)", "kinetic_energy"),
+        ] {
+            let err = SceneDesc::parse(&scene(diffuse)).unwrap_err();
+            assert!(
+                matches!(err, SceneError::Diffuse(name) if name == range),
+                "{err}"
+            );
+        }
+    }
+}
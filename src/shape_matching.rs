@@ -0,0 +1,70 @@
+use std::ops::Range;
+
+use crate::precision::{Matrix, Real, Rotation, Vector};
+
+// a cluster of particles pulled towards a rigidly transformed copy of its rest shape
+// (Müller et al. 2005)
+#[derive(Clone, Debug)]
+pub struct ShapeMatching {
+    pub particles: Range<usize>,
+    pub stiffness: Real, // 1 is rigid, lower values give a softer body
+    rest: Vec<Vector>,   // rest positions relative to the rest center of mass
+    rotation: Rotation,  // the last extracted rotation, warm starts the next one
+}
+
+impl ShapeMatching {
+    pub fn new(particles: Range<usize>, positions: &[Vector], stiffness: Real) -> Self {
+        let positions = &positions[particles.clone()];
+        let center = positions.iter().copied().sum::<Vector>() / positions.len() as Real;
+        Self {
+            particles,
+            stiffness,
+            rest: positions.iter().map(|p| *p - center).collect(),
+            rotation: Rotation::IDENTITY,
+        }
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+
+    // move the particles of the body towards their goal positions
+    pub fn solve(&mut self, positions: &mut [Vector]) {
+        let positions = &mut positions[self.particles.clone()];
+        let center = positions.iter().copied().sum::<Vector>() / positions.len() as Real;
+
+        let mut a = Matrix::ZERO;
+        for (p, q) in positions.iter().zip(&self.rest) {
+            let d = *p - center;
+            a += Matrix::from_cols(d * q.x, d * q.y, d * q.z);
+        }
+        self.rotation = extract_rotation(&a, self.rotation);
+
+        for (p, q) in positions.iter_mut().zip(&self.rest) {
+            let goal = center + self.rotation * *q;
+            *p += self.stiffness * (goal - *p);
+        }
+    }
+}
+
+// rotational part of a, starting from the guess q (Müller et al. 2016)
+fn extract_rotation(a: &Matrix, mut q: Rotation) -> Rotation {
+    for _ in 0..20 {
+        let r = Matrix::from_quat(q);
+        let omega = (r.x_axis.cross(a.x_axis)
+            + r.y_axis.cross(a.y_axis)
+            + r.z_axis.cross(a.z_axis))
+            / ((r.x_axis.dot(a.x_axis) + r.y_axis.dot(a.y_axis) + r.z_axis.dot(a.z_axis)).abs()
+                + 1e-9);
+        let w = omega.length();
+        if w < 1e-9 {
+            break;
+        }
+        q = (Rotation::from_axis_angle(omega / w, w) * q).normalize();
+    }
+    q
+}
@@ -1,48 +1,166 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
 use bevy::prelude::*;
 use rayon::prelude::*;
 
-#[derive(Resource)]
+use crate::boundary::{Boundary, SlipMode, Surface, box_surface};
+use crate::checkpoint::{BodyState, Checkpoint, CheckpointError, SimState};
+use crate::diffuse::{DiffuseSystem, advect};
+use crate::force_field::ForceField;
+use crate::material::Material;
+use crate::params::SimParams;
+use crate::precision::{Real, Rotation, Vector, vector};
+use crate::rigid_body::RigidBody;
+use crate::scene_desc::SceneDesc;
+use crate::shape_matching::ShapeMatching;
+
+// a particle touching a tank face or boundary during the current step
+#[derive(Clone, Copy, Debug)]
+struct Contact {
+    normal: Vector,
+    velocity: Vector, // velocity of the touched surface
+    approach: Real,   // normal velocity of the particle relative to the surface before the solve
+    surface: Surface,
+}
+
+// what a boundary sample is attached to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SampleOwner {
+    Tank,
+    Boundary(usize),
+    RigidBody(usize),
+}
+
+// the parts of a time step, simulate_timestep runs them in the order of step_phases
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Phase {
+    Integrate,          // move boundaries, rigid bodies and particles under the external forces
+    Collisions,         // push particles out of the tank walls, boundaries and rigid bodies
+    BuildHashtable,     // sort the boundary samples and the particles into the grid
+    DetectNeighbor,     // neighbor lists from the grid
+    Lambda,             // density constraint multipliers
+    PositionCorrection, // density, shape matching and granular contact corrections
+    VelocityUpdate,     // velocities from the corrected positions, rigid body coupling
+    Diffuse,            // spray, foam and bubbles
+}
+
+impl Phase {
+    pub const ALL: [Phase; 8] = [
+        Phase::Integrate,
+        Phase::Collisions,
+        Phase::BuildHashtable,
+        Phase::DetectNeighbor,
+        Phase::Lambda,
+        Phase::PositionCorrection,
+        Phase::VelocityUpdate,
+        Phase::Diffuse,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Integrate => "integrate",
+            Phase::Collisions => "collisions",
+            Phase::BuildHashtable => "build_hashtable",
+            Phase::DetectNeighbor => "detect_neighbor",
+            Phase::Lambda => "lambda",
+            Phase::PositionCorrection => "position_correction",
+            Phase::VelocityUpdate => "velocity_update",
+            Phase::Diffuse => "diffuse",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GrabMode {
+    Attract,
+    Repel,
+}
+
+// a user interaction acting on the particles within radius of center
+// attraction is a damped spring towards center, repulsion pushes radially outwards
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Grab {
+    pub center: Vector, // world frame
+    pub radius: Real,
+    pub strength: Real, // spring stiffness in 1/s^2
+    pub mode: GrabMode,
+}
+
+impl Grab {
+    fn acceleration(&self, center: Vector, pos: Vector, vel: Vector) -> Vector {
+        let d = center - pos;
+        let dist = d.length();
+        if dist >= self.radius {
+            return Vector::ZERO;
+        }
+        let falloff = 1.0 - dist / self.radius;
+        match self.mode {
+            // critically damped for a unit falloff
+            GrabMode::Attract => falloff * (self.strength * d - 2.0 * self.strength.sqrt() * vel),
+            GrabMode::Repel => -falloff * self.strength * self.radius * d.normalize_or_zero(),
+        }
+    }
+}
+
+#[derive(Resource, Clone)]
 pub struct Simulator {
-    pub position: Vec<Vec3>, // Particle Position
-    velocity: Vec<Vec3>,     // Particle Velocity
-    pub color: Vec<Vec3>,
+    pub position: Vec<Vector>, // Particle Position, in the tank frame
+    velocity: Vec<Vector>,     // Particle Velocity
+    pub color: Vec<Vector>,
+    pub material: Vec<Material>,
+    pub body: Vec<Option<usize>>, // the shape matching body a particle belongs to
 
-    position_: Vec<Vec3>,
+    position_: Vec<Vector>,
     neighbor: Vec<Vec<usize>>,
+    lambda: Vec<Real>, // density constraint multipliers of the current solver iteration
+    contact: Vec<Option<Contact>>,
 
     cell_x: usize,
     cell_y: usize,
     cell_z: usize,
     num_cell: usize,
+    cell_size: Vector, // h, or a bit more to fit a whole number of cells along periodic axes
     hashtable: Vec<usize>,
     hashtableindex: Vec<usize>,
 
-    pub scene_id: i32,
+    // particles sampling the tank walls, boundaries and rigid bodies (Akinci et al. 2012)
+    boundary_owner: Vec<SampleOwner>,
+    boundary_local: Vec<Vector>,    // sample position in the body frame
+    boundary_volume: Vec<Real>,     // inverse number density among the samples of the body
+    boundary_position: Vec<Vector>, // in the tank frame, moved with the bodies every step
+    boundary_neighbor: Vec<Vec<usize>>,
+    boundary_hashtable: Vec<usize>,
+    boundary_hashtableindex: Vec<usize>,
+
+    scene: SceneDesc,
     pub scene_changed: bool,
-    pub tank: Vec3, // tank size
-    rel_water: Vec3,
-    offset: Vec3,
-    pub slide_pos: f32,
-    slide_vel: f32,
-    slide_dir: i32,
+    pub tank: Vector, // tank size
+    pub tank_surface: Surface,
+    pub periodic: BVec3,     // axes wrapping around instead of ending at walls
+    tank_rotation: Rotation, // orientation of the tank frame in the world
+    grab: Option<Grab>,
+    pub boundaries: Vec<Boundary>, // walls and colliders inside the tank
+    pub force_fields: Vec<ForceField>, // external forces in the tank frame
+    bodies: Vec<ShapeMatching>,
+    pub rigid_bodies: Vec<RigidBody>, // floating and sinking objects, coupled with the particles
+    diffuse: Option<DiffuseSystem>,   // spray, foam and bubbles, if the scene asks for them
+    pub time: Real,                   // simulated time since the last reset
 
     pub num_sphere: usize,
-    pub radius: f32, // radius of particles
+    pub radius: Real, // radius of particles, fixed at scene setup
 
-    rest_density: f32,
-    num: usize,
-    ratio: f32, // ratio between max neighbor distance and particle radius
-    h: f32,     // max neighbor distance
+    rest_density: Real,
+    h: Real, // max neighbor distance
 
-    solver_iteration: usize,
-    relaxation: f32,
-    damping: f32,
-    gravity: Vec3,
+    params: SimParams,
 }
 
-const INV_PI: f32 = 0.318301;
+const INV_PI: Real = 0.318301;
+const SURFACE_NORMAL: Real = 0.5; // normal length above which a particle is at the surface
+const WATER_DENSITY: Real = 1000.0; // kg/m^3, gives the particles a mass for the rigid bodies
 
-fn poly6(r: &Vec3, h: f32) -> f32 {
+fn poly6(r: &Vector, h: Real) -> Real {
     let r2 = r.length_squared();
     let h2 = h * h;
     let diff = h2 - r2;
@@ -56,11 +174,11 @@ fn poly6(r: &Vec3, h: f32) -> f32 {
     coeff * diff3 / h9
 }
 
-fn grad_spiky(r: &Vec3, h: f32) -> Vec3 {
+fn grad_spiky(r: &Vector, h: Real) -> Vector {
     let r2 = r.length_squared();
     let h2 = h * h;
     if r2 > h2 {
-        return Vec3::ZERO;
+        return Vector::ZERO;
     }
     let coeff = -45.0 * INV_PI;
     let r_norm = r2.sqrt();
@@ -70,101 +188,381 @@ fn grad_spiky(r: &Vec3, h: f32) -> Vec3 {
     coeff * diff * diff / (h6 * r_norm.max(1e-24)) * r
 }
 
+// the inverse of the kernel sum over the nearby samples, the volume each sample represents
+fn sample_volumes(samples: &[Vector], h: Real) -> Vec<Real> {
+    let cell = |p: Vector| (p / h).floor().as_ivec3();
+    let mut grid: HashMap<IVec3, Vec<usize>> = HashMap::new();
+    for (k, &p) in samples.iter().enumerate() {
+        grid.entry(cell(p)).or_default().push(k);
+    }
+    samples
+        .par_iter()
+        .map(|&p| {
+            let c = cell(p);
+            let mut sum = 0.0;
+            for i in -1..=1 {
+                for j in -1..=1 {
+                    for k in -1..=1 {
+                        for &other in grid.get(&(c + IVec3::new(i, j, k))).into_iter().flatten() {
+                            sum += poly6(&(p - samples[other]), h);
+                        }
+                    }
+                }
+            }
+            1.0 / sum
+        })
+        .collect()
+}
+
+// counting sort of items into grid cells, items without a cell are left out
+// index gets the start of every cell and one past the end, table the items cell by cell
+// the scatter is serial so the items of a cell keep their order whatever the thread count,
+// a parallel one would change the neighbor order and with it the rounding of every sum
+// over neighbors
+fn fill_grid(offsets: &[Option<usize>], index: &mut [usize], table: &mut Vec<usize>) {
+    let num_cell = index.len() - 1;
+    index.fill(0);
+    for &offset in offsets.iter().flatten() {
+        index[offset] += 1;
+    }
+    let mut prefix_sum = 0;
+    for count in &mut index[..num_cell] {
+        prefix_sum += *count;
+        *count = prefix_sum;
+    }
+    index[num_cell] = prefix_sum;
+
+    table.clear();
+    table.resize(prefix_sum, 0);
+    for (k, offset) in offsets.iter().enumerate() {
+        if let Some(offset) = *offset {
+            index[offset] -= 1;
+            table[index[offset]] = k;
+        }
+    }
+}
+
 #[allow(dead_code)]
 impl Simulator {
-    pub fn new() -> Self {
+    pub fn new(params: SimParams) -> Self {
         // a new simulator without scene initialization
         // the scene will be initialized in reset_system()
         Self {
             position: Vec::new(),
             velocity: Vec::new(),
             color: Vec::new(),
+            material: Vec::new(),
+            body: Vec::new(),
 
             position_: Vec::new(),
             neighbor: Vec::new(),
+            lambda: Vec::new(),
+            contact: Vec::new(),
 
             cell_x: 0,
             cell_y: 0,
             cell_z: 0,
             num_cell: 0,
+            cell_size: Vector::ZERO,
             hashtable: Vec::new(),
             hashtableindex: Vec::new(),
 
-            scene_id: 0,
+            boundary_owner: Vec::new(),
+            boundary_local: Vec::new(),
+            boundary_volume: Vec::new(),
+            boundary_position: Vec::new(),
+            boundary_neighbor: Vec::new(),
+            boundary_hashtable: Vec::new(),
+            boundary_hashtableindex: Vec::new(),
+
+            scene: SceneDesc::default(),
             scene_changed: true,
-            tank: Vec3::ZERO,
-            rel_water: Vec3::ZERO,
-            offset: Vec3::ZERO,
-            slide_pos: 0.0,
-            slide_vel: 0.0,
-            slide_dir: 0,
+            tank: Vector::ZERO,
+            tank_surface: Surface::default(),
+            periodic: BVec3::FALSE,
+            tank_rotation: Rotation::IDENTITY,
+            grab: None,
+            boundaries: Vec::new(),
+            force_fields: Vec::new(),
+            bodies: Vec::new(),
+            rigid_bodies: Vec::new(),
+            diffuse: None,
+            time: 0.0,
 
             num_sphere: 0,
-            radius: 0.015,
+            radius: params.radius(),
 
             rest_density: 0.0,
-            num: 9,
-            ratio: 3.0,
             h: 0.0,
 
-            solver_iteration: 5,
-            relaxation: 1e3,
-            damping: 0.999,
-            gravity: Vec3::new(0.0, -9.81, 0.0), // 默认重力加速度
+            params,
+        }
+    }
+
+    pub fn scene(&self) -> &SceneDesc {
+        &self.scene
+    }
+
+    // the new scene is set up on the next reset
+    pub fn set_scene(&mut self, scene: SceneDesc) {
+        self.scene = scene;
+        self.scene_changed = true;
+    }
+
+    pub fn bodies(&self) -> &[ShapeMatching] {
+        &self.bodies
+    }
+
+    pub fn diffuse(&self) -> Option<&DiffuseSystem> {
+        self.diffuse.as_ref()
+    }
+
+    pub fn velocity(&self) -> &[Vector] {
+        &self.velocity
+    }
+
+    // the positions the solver works on during a step, equal to position between steps
+    pub fn predicted_position(&self) -> &[Vector] {
+        &self.position_
+    }
+
+    // write the particles as an ascii PLY point cloud, in the tank frame
+    pub fn write_ply(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "ply")?;
+        writeln!(writer, "format ascii 1.0")?;
+        writeln!(writer, "comment material 0 fluid, 1 granular, 2 solid")?;
+        writeln!(writer, "element vertex {}", self.num_sphere)?;
+        for name in ["x", "y", "z", "vx", "vy", "vz"] {
+            writeln!(writer, "property float {name}")?;
+        }
+        writeln!(writer, "property uchar material")?;
+        writeln!(writer, "end_header")?;
+        for i in 0..self.num_sphere {
+            let (x, v) = (self.position[i], self.velocity[i]);
+            let material = match (self.body[i], self.material[i]) {
+                (Some(_), _) => 2,
+                (None, Material::Fluid) => 0,
+                (None, Material::Granular { .. }) => 1,
+            };
+            writeln!(
+                writer,
+                "{} {} {} {} {} {} {}",
+                x.x, x.y, x.z, v.x, v.y, v.z, material
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn params(&self) -> &SimParams {
+        &self.params
+    }
+
+    // max neighbor distance, the kernel support
+    pub fn neighbor_radius(&self) -> Real {
+        self.h
+    }
+
+    pub fn neighbors(&self, index: usize) -> &[usize] {
+        &self.neighbor[index]
+    }
+
+    // indices into boundary_positions
+    pub fn boundary_neighbors(&self, index: usize) -> &[usize] {
+        &self.boundary_neighbor[index]
+    }
+
+    // samples of the tank walls, boundaries and rigid bodies, in the tank frame
+    pub fn boundary_positions(&self) -> &[Vector] {
+        &self.boundary_position
+    }
+
+    // rebuild the grids and the neighbor lists after placing particles in position by hand
+    // only the neighbor search sees them, stepping still needs a consistent particle count
+    pub fn find_neighbors(&mut self) {
+        self.position_.clone_from(&self.position);
+        self.build_boundary_hashtable();
+        self.build_hashtable();
+        self.search_neighbors();
+    }
+
+    // density of every particle relative to the rest density, over the neighbors of the last step
+    pub fn relative_density(&self) -> Vec<Real> {
+        (0..self.num_sphere)
+            .into_par_iter()
+            .map(|i| self.calc_density(i) / self.rest_density)
+            .collect()
+    }
+
+    // radius, ratio and rest neighbors take effect on the next reset
+    pub fn set_params(&mut self, params: SimParams) {
+        self.params = params;
+    }
+
+    pub fn tank_rotation(&self) -> Rotation {
+        self.tank_rotation
+    }
+
+    // turn the tank about its center, the particles keep their world positions
+    // and the walls push them along during the next step
+    pub fn set_tank_rotation(&mut self, rotation: Rotation) {
+        let rotation = rotation.normalize();
+        let delta = rotation.inverse() * self.tank_rotation;
+        for i in 0..self.num_sphere {
+            self.position[i] = delta * self.position[i];
+            self.position_[i] = delta * self.position_[i];
+            self.velocity[i] = delta * self.velocity[i];
+        }
+        for body in &mut self.rigid_bodies {
+            body.rotate_frame(delta);
+        }
+        if let Some(diffuse) = &mut self.diffuse {
+            for p in &mut diffuse.particles {
+                p.position = delta * p.position;
+                p.velocity = delta * p.velocity;
+            }
         }
+        self.tank_rotation = rotation;
+    }
+
+    // tank frame to world frame
+    pub fn to_world(&self, p: Vector) -> Vector {
+        self.tank_rotation * p
+    }
+
+    // world frame to tank frame
+    pub fn to_local(&self, p: Vector) -> Vector {
+        self.tank_rotation.inverse() * p
+    }
+
+    pub fn grab(&self) -> Option<Grab> {
+        self.grab
+    }
+
+    pub fn set_grab(&mut self, grab: Option<Grab>) {
+        self.grab = grab;
+    }
+
+    // distance along a world space ray to the first particle it hits
+    pub fn raycast(&self, origin: Vector, dir: Vector) -> Option<Real> {
+        let origin = self.to_local(origin);
+        let dir = self.to_local(dir).normalize();
+        let r2 = self.radius * self.radius;
+        self.position
+            .iter()
+            .filter_map(|&pos| {
+                let t = (pos - origin).dot(dir);
+                let d2 = (origin + t * dir).distance_squared(pos);
+                (t > 0.0 && d2 < r2).then(|| t - (r2 - d2).sqrt())
+            })
+            .min_by(|a, b| a.total_cmp(b))
     }
 
-    fn calc_density(&self, index: usize) -> f32 {
+    fn calc_density(&self, index: usize) -> Real {
         let mut density = 0.0;
         let pos = self.position_[index];
         for &neighbor_index in &self.neighbor[index] {
-            let r = pos - self.position_[neighbor_index];
+            let r = self.min_image(pos - self.position_[neighbor_index]);
             let d = poly6(&r, self.h);
             density += d;
         }
+        for &b in &self.boundary_neighbor[index] {
+            let r = self.boundary_offset(pos, b);
+            density += self.boundary_psi(b) * poly6(&r, self.h);
+        }
         density
     }
 
-    fn calc_constraint(&self, index: usize) -> f32 {
+    // mass of a boundary sample, the fluid particles have unit mass
+    fn boundary_psi(&self, index: usize) -> Real {
+        self.rest_density * self.boundary_volume[index]
+    }
+
+    // mass of a particle in kg, the water filling one cell of the initial particle lattice
+    fn particle_mass(&self) -> Real {
+        let r = self.radius;
+        WATER_DENSITY * 4.0 * (3.0 as Real).sqrt() * r * r * r
+    }
+
+    fn calc_constraint(&self, index: usize) -> Real {
         self.calc_density(index) / self.rest_density - 1.0
     }
 
-    fn calc_grad_constraint(&self, index: usize, neighbor_index: usize) -> Vec3 {
+    fn calc_grad_constraint(&self, index: usize, neighbor_index: usize) -> Vector {
         let grad_c = if neighbor_index == index {
-            let mut grad_c = Vec3::ZERO;
+            let mut grad_c = Vector::ZERO;
             for &neighbor in &self.neighbor[index] {
-                let r = self.position_[index] - self.position_[neighbor];
+                let r = self.min_image(self.position_[index] - self.position_[neighbor]);
                 grad_c += grad_spiky(&r, self.h);
             }
+            for &b in &self.boundary_neighbor[index] {
+                let r = self.boundary_offset(self.position_[index], b);
+                grad_c += self.boundary_psi(b) * grad_spiky(&r, self.h);
+            }
             grad_c
         } else {
-            let r = self.position_[index] - self.position_[neighbor_index];
+            let r = self.min_image(self.position_[index] - self.position_[neighbor_index]);
             -grad_spiky(&r, self.h)
         };
         grad_c / self.rest_density
     }
 
     fn handle_collisions(&mut self) {
+        let min = -0.5 * self.tank + self.radius;
+        let max = 0.5 * self.tank - self.radius;
+        // only the walls hold the particles that wrap around periodic axes
+        let open = Vector::select(self.periodic, Vector::INFINITY, max);
+        let mass = self.particle_mass();
         for i in 0..self.num_sphere {
-            if self.position_[i].x < -0.5 * self.tank.x + self.radius {
-                self.position_[i].x = -0.5 * self.tank.x + self.radius;
-            }
-            if self.position_[i].x > 0.5 * self.tank.x * self.slide_pos - self.radius {
-                self.position_[i].x = 0.5 * self.tank.x * self.slide_pos - self.radius;
-            }
-
-            if self.position_[i].y < -0.5 * self.tank.y + self.radius {
-                self.position_[i].y = -0.5 * self.tank.y + self.radius;
+            for boundary in &self.boundaries {
+                if let Some((pos, normal)) = boundary.project(self.position_[i], self.radius) {
+                    let velocity = boundary.velocity_at(pos);
+                    self.position_[i] = pos;
+                    self.contact[i] = Some(Contact {
+                        normal,
+                        velocity,
+                        approach: (self.velocity[i] - velocity).dot(normal),
+                        surface: boundary.surface,
+                    });
+                }
             }
-            if self.position_[i].y > 0.5 * self.tank.y - self.radius {
-                self.position_[i].y = 0.5 * self.tank.y - self.radius;
+            for body in &mut self.rigid_bodies {
+                let Some((pos, normal)) =
+                    body.shape
+                        .shape()
+                        .project(body.pose(), self.position_[i], self.radius)
+                else {
+                    continue;
+                };
+                body.exchange(pos, mass, pos - self.position_[i]);
+                let velocity = body.velocity_at(pos);
+                self.position_[i] = pos;
+                self.contact[i] = Some(Contact {
+                    normal,
+                    velocity,
+                    approach: (self.velocity[i] - velocity).dot(normal),
+                    surface: body.surface,
+                });
             }
 
-            if self.position_[i].z < -0.5 * self.tank.z + self.radius {
-                self.position_[i].z = -0.5 * self.tank.z + self.radius;
-            }
-            if self.position_[i].z > 0.5 * self.tank.z - self.radius {
-                self.position_[i].z = 0.5 * self.tank.z - self.radius;
+            // the tank comes last so that particles always stay inside the grid
+            // solids are kept inside, shape matching cannot hold a body cut by the seam
+            let (pos, clamped) = if self.body[i].is_some() {
+                let pos = self.position_[i];
+                (pos, pos.clamp(min, max))
+            } else {
+                let pos = self.wrap(self.position_[i]);
+                (pos, pos.clamp(-open, open))
+            };
+            self.position_[i] = pos;
+            if clamped != pos {
+                let normal = (clamped - pos).normalize();
+                self.position_[i] = clamped;
+                self.contact[i] = Some(Contact {
+                    normal,
+                    velocity: Vector::ZERO,
+                    approach: self.velocity[i].dot(normal),
+                    surface: self.tank_surface,
+                });
             }
         }
     }
@@ -176,122 +574,210 @@ impl Simulator {
     }
 
     fn build_hashtable(&mut self) {
-        self.hashtable.fill(0);
-        self.hashtableindex.fill(0);
+        let offsets: Vec<Option<usize>> = self
+            .position_
+            .par_iter()
+            .map(|&pos| Some(self.index2grid_offset(self.cell_of(pos))))
+            .collect();
+        fill_grid(&offsets, &mut self.hashtableindex, &mut self.hashtable);
+    }
 
-        for i in 0..self.num_sphere {
-            let pos = self.position_[i];
-            let index = ((pos + 0.5 * self.tank) / self.h).as_uvec3() + 1;
-            let offset = self.index2grid_offset(index);
-            self.hashtableindex[offset] += 1;
+    fn build_boundary_hashtable(&mut self) {
+        for ((pos, owner), local) in self
+            .boundary_position
+            .iter_mut()
+            .zip(&self.boundary_owner)
+            .zip(&self.boundary_local)
+        {
+            *pos = match *owner {
+                SampleOwner::Tank => *local,
+                SampleOwner::Boundary(b) => self.boundaries[b].pose().transform_point(*local),
+                SampleOwner::RigidBody(b) => self.rigid_bodies[b].pose().transform_point(*local),
+            };
         }
 
-        let mut prefix_sum = 0;
-        for i in 0..self.num_cell {
-            prefix_sum += self.hashtableindex[i];
-            self.hashtableindex[i] = prefix_sum;
+        let half = 0.5 * self.tank;
+        let offsets: Vec<Option<usize>> = self
+            .boundary_position
+            .iter()
+            .map(|&pos| {
+                ((pos.abs() - half).max_element() <= 1e-4)
+                    .then(|| self.index2grid_offset(self.cell_of(pos)))
+            })
+            .collect();
+
+        fill_grid(
+            &offsets,
+            &mut self.boundary_hashtableindex,
+            &mut self.boundary_hashtable,
+        );
+    }
+
+    // size of the neighbor grid, one cell of padding around the tank on every side
+    pub fn cell_count(&self) -> UVec3 {
+        UVec3::new(self.cell_x as u32, self.cell_y as u32, self.cell_z as u32)
+    }
+
+    // grid cell of a position in the tank frame, positions outside the tank go to the
+    // nearest cell inside it, or wrap around periodic axes, so the 3x3x3 stencil around
+    // a cell never leaves the grid
+    pub fn cell_of(&self, pos: Vector) -> UVec3 {
+        let index = ((self.wrap(pos) + 0.5 * self.tank) / self.cell_size).as_uvec3() + 1;
+        index.clamp(UVec3::ONE, self.cell_count() - 2)
+    }
+
+    // grid offsets of the 3x3x3 cells around a cell, continued on the other side of the tank
+    // along periodic axes, every cell only once when such an axis has less than 3 cells
+    fn stencil(&self, cell: UVec3) -> impl Iterator<Item = usize> + '_ {
+        let count = self.cell_count() - 2;
+        let around = |axis: usize| {
+            let (c, n) = (cell[axis], count[axis]);
+            if self.periodic.test(axis) {
+                ([(c + n - 2) % n + 1, c, c % n + 1], n.min(3) as usize)
+            } else {
+                ([c - 1, c, c + 1], 3)
+            }
+        };
+        let ((xs, nx), (ys, ny), (zs, nz)) = (around(0), around(1), around(2));
+        xs.into_iter().take(nx).flat_map(move |x| {
+            ys.into_iter().take(ny).flat_map(move |y| {
+                zs.into_iter()
+                    .take(nz)
+                    .map(move |z| self.index2grid_offset(UVec3::new(x, y, z)))
+            })
+        })
+    }
+
+    // the shortest of the vectors between the periodic images of two points
+    pub fn min_image(&self, d: Vector) -> Vector {
+        if !self.periodic.any() {
+            return d;
         }
-        self.hashtableindex[self.num_cell] = prefix_sum;
+        Vector::select(self.periodic, d - self.tank * (d / self.tank).round(), d)
+    }
 
-        for i in 0..self.num_sphere {
-            let pos = self.position_[i];
-            let index = ((pos + 0.5 * self.tank) / self.h).as_uvec3() + 1;
-            let offset = self.index2grid_offset(index);
-            self.hashtableindex[offset] -= 1;
-            self.hashtable[self.hashtableindex[offset]] = i;
+    // from a boundary sample to a position, only the tank walls continue across the seam,
+    // colliders and rigid bodies are felt where they are
+    fn boundary_offset(&self, pos: Vector, b: usize) -> Vector {
+        let d = pos - self.boundary_position[b];
+        match self.boundary_owner[b] {
+            SampleOwner::Tank => self.min_image(d),
+            _ => d,
         }
     }
 
-    fn intergrate_particles(&mut self, dt: f32) {
-        for i in 0..self.num_sphere {
-            self.velocity[i] += self.gravity * dt;
-            self.position_[i] += self.velocity[i] * dt;
+    // the image of a position inside the tank along periodic axes
+    fn wrap(&self, pos: Vector) -> Vector {
+        if !self.periodic.any() {
+            return pos;
         }
+        let wrapped = pos - self.tank * ((pos + 0.5 * self.tank) / self.tank).floor();
+        Vector::select(self.periodic, wrapped, pos)
     }
 
-    fn detect_neighbor(&mut self) {
-        self.handle_collisions();
-        self.build_hashtable();
-        self.neighbor.clear();
-        self.neighbor.resize(self.num_sphere, Vec::new());
+    // particles the last grid build put in a cell
+    pub fn cell_particles(&self, cell: UVec3) -> &[usize] {
+        let offset = self.index2grid_offset(cell);
+        &self.hashtable[self.hashtableindex[offset]..self.hashtableindex[offset + 1]]
+    }
 
+    fn intergrate_particles(&mut self, dt: Real) {
+        self.contact.fill(None);
+        // gravity is given in the world frame
+        let gravity = self.tank_rotation.inverse() * self.params.gravity();
+        let grab = self.grab.map(|grab| (grab, self.to_local(grab.center)));
+        let force_fields = &self.force_fields;
+        let time = self.time;
+        self.velocity
+            .par_iter_mut()
+            .zip(self.position_.par_iter_mut())
+            .zip(self.position.par_iter())
+            .for_each(|((vel, pos_), &pos)| {
+                let mut acc = gravity;
+                for field in force_fields {
+                    acc += field.acceleration(pos, *vel, time);
+                }
+                if let Some((grab, center)) = grab {
+                    acc += grab.acceleration(center, pos, *vel);
+                }
+                *vel += acc * dt;
+                *pos_ += *vel * dt;
+            });
+    }
+
+    // neighbor lists of the predicted positions, the grids have to be built already
+    fn search_neighbors(&mut self) {
+        let num = self.position_.len();
+        let mut neighbor = std::mem::take(&mut self.neighbor);
+        let mut boundary_neighbor = std::mem::take(&mut self.boundary_neighbor);
+        neighbor.clear();
+        neighbor.resize(num, Vec::new());
+        boundary_neighbor.resize(num, Vec::new());
+
+        let cells: Vec<UVec3> = self
+            .position_
+            .par_iter()
+            .map(|&p| self.cell_of(p))
+            .collect();
         let position_ = &self.position_;
         let hashtable = &self.hashtable;
         let hashtableindex = &self.hashtableindex;
         let h = self.h;
-        let tank = self.tank;
-        let index2grid_offset = |index: UVec3| {
-            index.x as usize * self.cell_y * self.cell_z
-                + index.y as usize * self.cell_z
-                + index.z as usize
-        };
 
-        self.neighbor
+        neighbor
             .par_iter_mut()
             .enumerate()
             .for_each(|(p, neighbors)| {
                 let pos = position_[p];
-                let grid_index = ((pos + 0.5 * tank) / h).as_uvec3() + 1;
-
-                for i in -1..=1 {
-                    for j in -1..=1 {
-                        for k in -1..=1 {
-                            let offset = index2grid_offset(UVec3::new(
-                                (grid_index.x as i32 + i) as u32,
-                                (grid_index.y as i32 + j) as u32,
-                                (grid_index.z as i32 + k) as u32,
-                            ));
-                            let start = hashtableindex[offset];
-                            let end = hashtableindex[offset + 1];
-
-                            for idx in start..end {
-                                let neighbor_index = hashtable[idx];
-                                if neighbor_index != p {
-                                    let d = position_[neighbor_index] - pos;
-                                    if d.length_squared() < h * h {
-                                        neighbors.push(neighbor_index);
-                                    }
-                                }
+                for offset in self.stencil(cells[p]) {
+                    let start = hashtableindex[offset];
+                    let end = hashtableindex[offset + 1];
+
+                    for &neighbor_index in &hashtable[start..end] {
+                        if neighbor_index != p {
+                            let d = self.min_image(position_[neighbor_index] - pos);
+                            if d.length_squared() < h * h {
+                                neighbors.push(neighbor_index);
                             }
                         }
                     }
                 }
             });
 
-        // for p in 0..self.num_sphere {
-        //     let pos = self.position_[p];
-        //     let grid_index = ((pos + 0.5 * self.tank) / self.h).as_uvec3() + 1;
-        //     for i in -1..=1 {
-        //         for j in -1..=1 {
-        //             for k in -1..=1 {
-        //                 let offset = self.index2grid_offset(UVec3::new(
-        //                     (grid_index.x as i32 + i) as u32,
-        //                     (grid_index.y as i32 + j) as u32,
-        //                     (grid_index.z as i32 + k) as u32,
-        //                 ));
-        //                 let start = self.hashtableindex[offset];
-        //                 let end = self.hashtableindex[offset + 1];
-
-        //                 for idx in start..end {
-        //                     let neighbor_index = self.hashtable[idx];
-        //                     if neighbor_index != p {
-        //                         let d = self.position_[neighbor_index] - pos;
-        //                         if d.length_squared() < self.h * self.h {
-        //                             self.neighbor[p].push(neighbor_index);
-        //                         }
-        //                     }
-        //                 }
-        //             }
-        //         }
-        //     }
-        // }
-    }
-
-    fn constraint_solve(&mut self) {
-        let mut lambda = vec![0.0; self.num_sphere];
-        let mut delta_pos = vec![Vec3::ZERO; self.num_sphere];
+        let boundary_hashtable = &self.boundary_hashtable;
+        let boundary_hashtableindex = &self.boundary_hashtableindex;
+        boundary_neighbor
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(p, neighbors)| {
+                neighbors.clear();
+                let pos = position_[p];
+                for offset in self.stencil(cells[p]) {
+                    let start = boundary_hashtableindex[offset];
+                    let end = boundary_hashtableindex[offset + 1];
+
+                    for &b in &boundary_hashtable[start..end] {
+                        let d = self.boundary_offset(pos, b);
+                        if d.length_squared() < h * h {
+                            neighbors.push(b);
+                        }
+                    }
+                }
+            });
+        self.neighbor = neighbor;
+        self.boundary_neighbor = boundary_neighbor;
+    }
+
+    fn compute_lambda(&mut self) {
+        let mut lambda = std::mem::take(&mut self.lambda);
+        lambda.resize(self.num_sphere, 0.0);
 
+        // only fluid particles carry pressure, granular ones just get pushed around by it
         lambda.par_iter_mut().enumerate().for_each(|(i, lambda_i)| {
+            *lambda_i = 0.0;
+            if !self.material[i].is_fluid() {
+                return;
+            }
             let numerator = self.calc_constraint(i);
             let mut denominator = 0.0;
             for &j in &self.neighbor[i] {
@@ -299,25 +785,21 @@ impl Simulator {
                 denominator += grad_c.length_squared();
             }
             denominator += self.calc_grad_constraint(i, i).length_squared();
-            denominator += self.relaxation;
+            denominator += self.params.relaxation();
             *lambda_i = -numerator / denominator;
         });
+        self.lambda = lambda;
+    }
+
+    // move the particles by the density corrections, then solve the shape matching bodies
+    // and the granular contacts
+    fn correct_positions(&mut self) {
+        let lambda = std::mem::take(&mut self.lambda);
+        let mut delta_pos = vec![Vector::ZERO; self.num_sphere];
 
-        // for i in 0..self.num_sphere {
-        //     let numerator = self.calc_constraint(i);
-        //     let mut denominator = 0.0;
-        //     for &j in &self.neighbor[i] {
-        //         let grad_c = self.calc_grad_constraint(i, j);
-        //         denominator += grad_c.length_squared();
-        //     }
-        //     denominator += self.calc_grad_constraint(i, i).length_squared();
-        //     denominator += self.relaxation;
-        //     lambda[i] = -numerator / denominator;
-        // }
-
-        const K: f32 = 1e-5;
-        const N: i32 = 4;
-        let w = poly6(&vec3(0.3 * self.h, 0.0, 0.0), self.h);
+        let k = self.params.tensile_k();
+        let n = self.params.tensile_n();
+        let w = poly6(&vector(self.params.tensile_dq() * self.h, 0.0, 0.0), self.h);
 
         delta_pos
             .par_iter_mut()
@@ -328,101 +810,449 @@ impl Simulator {
                     if j == i {
                         continue;
                     }
-                    let r = pos - self.position_[j];
-                    let ratio = poly6(&r, self.h) / w;
-                    let s_corr = -K * f32::powi(ratio, N);
+                    let r = self.min_image(pos - self.position_[j]);
+                    let s_corr = if self.material[i].is_fluid() && self.material[j].is_fluid() {
+                        let ratio = poly6(&r, self.h) / w;
+                        -k * Real::powi(ratio, n)
+                    } else {
+                        0.0
+                    };
                     *delta_pos_i += (lambda[i] + lambda[j] + s_corr) * grad_spiky(&r, self.h);
                 }
+                // the walls only push back with the pressure of the particle itself
+                for &b in &self.boundary_neighbor[i] {
+                    let r = self.boundary_offset(pos, b);
+                    *delta_pos_i += lambda[i] * self.boundary_psi(b) * grad_spiky(&r, self.h);
+                }
                 *delta_pos_i /= self.rest_density;
             });
 
-        // for i in 0..self.num_sphere {
-        //     let pos = self.position_[i];
-        //     for &j in &self.neighbor[i] {
-        //         if j == i {
-        //             continue;
-        //         }
-        //         let r = pos - self.position_[j];
-        //         let ratio = poly6(&r, self.h) / w;
-        //         let s_corr = -K * f32::powi(ratio, N);
-        //         delta_pos[i] += (lambda[i] + lambda[j] + s_corr) * grad_spiky(&r, self.h);
-        //     }
-        //     delta_pos[i] /= self.rest_density;
-        // }
+        // the rigid bodies take the opposite of the pressure push they give the fluid
+        // only along their surface normal, otherwise the fluid beside a body would hang
+        // on its side walls and lift it
+        if !self.rigid_bodies.is_empty() {
+            let mass = self.particle_mass();
+            for (i, &lambda_i) in lambda.iter().enumerate() {
+                for &b in &self.boundary_neighbor[i] {
+                    let SampleOwner::RigidBody(k) = self.boundary_owner[b] else {
+                        continue;
+                    };
+                    let point = self.boundary_position[b];
+                    let r = self.boundary_offset(self.position_[i], b);
+                    let n = self.rigid_bodies[k].normal_at(self.position_[i]);
+                    let push = lambda_i * self.boundary_psi(b) * grad_spiky(&r, self.h);
+                    let dx = push.dot(n) * n / self.rest_density;
+                    self.rigid_bodies[k].exchange(point, mass, dx);
+                }
+            }
+        }
+
+        for (pos, delta) in self.position_.iter_mut().zip(&delta_pos) {
+            *pos += *delta;
+        }
+        for body in &mut self.bodies {
+            body.solve(&mut self.position_);
+        }
+        self.solve_granular_contacts();
+        self.lambda = lambda;
+    }
 
+    // non-penetration and friction between touching granular particles (Macklin et al. 2014)
+    // and frictionless non-penetration against fluid particles, the density constraint
+    // alone lets fluid seep into the gaps of a solid
+    // solved gauss-seidel style, jacobi iterations converge too slowly to hold up a pile
+    fn solve_granular_contacts(&mut self) {
+        // shock propagation, lower particles weigh more so that the ones above rest on them
+        const SHOCK: Real = 20.0;
+        let up = -(self.tank_rotation.inverse() * self.params.gravity()).normalize_or_zero();
+        let d_min = 2.0 * self.radius;
         for i in 0..self.num_sphere {
-            self.position_[i] += delta_pos[i];
+            let Material::Granular {
+                static_friction: static_i,
+                kinetic_friction: kinetic_i,
+            } = self.material[i]
+            else {
+                continue;
+            };
+            for &j in &self.neighbor[i] {
+                let r = self.min_image(self.position_[i] - self.position_[j]);
+                let dist = r.length();
+                let depth = d_min - dist;
+                if depth <= 0.0 || dist < 1e-12 {
+                    continue;
+                }
+                let normal = r / dist;
+
+                let Material::Granular {
+                    static_friction: static_j,
+                    kinetic_friction: kinetic_j,
+                } = self.material[j]
+                else {
+                    let correction = 0.5 * depth * normal;
+                    self.position_[i] += correction;
+                    self.position_[j] -= correction;
+                    continue;
+                };
+                // particles of the same body are kept apart by shape matching
+                if j < i || (self.body[i].is_some() && self.body[i] == self.body[j]) {
+                    continue;
+                }
+                // share of the correction taken by i
+                let share = 1.0 / (1.0 + (SHOCK * r.dot(-up)).exp());
+                let mut correction = depth * normal;
+
+                // the relative tangential displacement during this step
+                let rel = self.min_image(self.position_[i] - self.position[i])
+                    - self.min_image(self.position_[j] - self.position[j]);
+                let tangent = rel - rel.dot(normal) * normal;
+                let slide = tangent.length();
+                let static_friction = 0.5 * (static_i + static_j);
+                let kinetic_friction = 0.5 * (kinetic_i + kinetic_j);
+                if slide < static_friction * depth {
+                    correction -= tangent;
+                } else if slide > 1e-12 {
+                    correction -= tangent * (kinetic_friction * depth / slide).min(1.0);
+                }
+
+                self.position_[i] += share * correction;
+                self.position_[j] -= (1.0 - share) * correction;
+            }
         }
-        self.handle_collisions();
     }
 
-    fn velocity_update(&mut self, dt: f32) {
+    fn velocity_update(&mut self, dt: Real) {
+        // slower impacts are treated as resting contact and do not bounce
+        let resting_speed = 2.0 * self.params.gravity().length() * dt;
         for i in 0..self.num_sphere {
-            self.velocity[i] = self.damping * (self.position_[i] - self.position[i]) / dt;
+            // particles wrapped around a periodic axis moved by the shortest way
+            let moved = self.min_image(self.position_[i] - self.position[i]);
+            self.velocity[i] = self.params.damping() * moved / dt;
             self.position[i] = self.position_[i];
+
+            // a touched boundary carries the particle along its normal
+            if let Some(contact) = self.contact[i] {
+                let n = contact.normal;
+                let surface = contact.surface;
+                let rel = self.velocity[i] - contact.velocity;
+                let vn = rel.dot(n);
+                let vt = rel - vn * n;
+
+                let bounce = if contact.approach < -resting_speed {
+                    -surface.restitution * contact.approach
+                } else {
+                    0.0
+                };
+                let vn = vn.max(bounce);
+                // granular particles bring their own friction to every surface
+                let (static_friction, kinetic_friction) = match self.material[i] {
+                    Material::Fluid => (surface.friction, surface.friction),
+                    Material::Granular {
+                        static_friction,
+                        kinetic_friction,
+                    } => (
+                        surface.friction.max(static_friction),
+                        surface.friction.max(kinetic_friction),
+                    ),
+                };
+                let vt = match surface.slip {
+                    SlipMode::NoSlip => Vector::ZERO,
+                    SlipMode::FreeSlip => {
+                        // coulomb friction, bounded by the normal velocity the surface took away
+                        let dvn = (vn - contact.approach).max(0.0);
+                        let speed = vt.length();
+                        if speed <= static_friction * dvn {
+                            Vector::ZERO
+                        } else {
+                            vt * (1.0 - kinetic_friction * dvn / speed).max(0.0)
+                        }
+                    }
+                };
+                self.velocity[i] = contact.velocity + vn * n + vt;
+            }
+        }
+    }
+
+    // call f with every fluid particle in the grid cells around pos
+    fn for_each_fluid_neighbor(&self, pos: Vector, mut f: impl FnMut(usize)) {
+        for offset in self.stencil(self.cell_of(pos)) {
+            let start = self.hashtableindex[offset];
+            let end = self.hashtableindex[offset + 1];
+            for &n in &self.hashtable[start..end] {
+                if self.material[n].is_fluid() {
+                    f(n);
+                }
+            }
+        }
+    }
+
+    // fluid density relative to the rest density and the kernel weighted fluid velocity at pos
+    fn sample_fluid(&self, pos: Vector) -> (Real, Vector) {
+        let mut density = 0.0;
+        let mut velocity = Vector::ZERO;
+        self.for_each_fluid_neighbor(pos, |j| {
+            let w = poly6(&self.min_image(pos - self.position[j]), self.h);
+            density += w;
+            velocity += w * self.velocity[j];
+        });
+        if density > 0.0 {
+            velocity /= density;
         }
+        (density / self.rest_density, velocity)
+    }
+
+    // emit spray, foam and bubbles where air gets trapped, at wave crests and
+    // at fast particles (Ihmsen et al. 2012), then move and age them
+    fn update_diffuse(&mut self, dt: Real) {
+        let Some(mut diffuse) = self.diffuse.take() else {
+            return;
+        };
+        // the grid is rebuilt from the positions at the end of the step
+        self.build_hashtable();
+        let h = self.h;
+        let weight = |r: Vector| (1.0 - r.length() / h).max(0.0);
+        let fluid_neighbors = |i: usize| {
+            self.neighbor[i]
+                .iter()
+                .copied()
+                .filter(|&j| self.material[j].is_fluid())
+        };
+
+        // unnormalized surface normals, they vanish inside the fluid
+        let normals: Vec<Vector> = (0..self.num_sphere)
+            .into_par_iter()
+            .map(|i| {
+                fluid_neighbors(i)
+                    .map(|j| {
+                        let r = self.min_image(self.position[i] - self.position[j]);
+                        r / h * weight(r)
+                    })
+                    .sum()
+            })
+            .collect();
+
+        let params = &diffuse.params;
+        let emission: Vec<Real> = (0..self.num_sphere)
+            .into_par_iter()
+            .map(|i| {
+                if !self.material[i].is_fluid() {
+                    return 0.0;
+                }
+                let (pos, vel) = (self.position[i], self.velocity[i]);
+                let n = normals[i].normalize_or_zero();
+                let mut trapped_air = 0.0;
+                let mut wave_crest = 0.0;
+                for j in fluid_neighbors(i) {
+                    let r = self.min_image(pos - self.position[j]);
+                    let w = weight(r);
+                    let rel = vel - self.velocity[j];
+                    let align = rel.normalize_or_zero().dot(r.normalize_or_zero());
+                    trapped_air += rel.length() * (1.0 - align) * w;
+                    // only neighbors behind the surface make it convex
+                    if r.dot(n) > 0.0 {
+                        wave_crest += (1.0 - n.dot(normals[j].normalize_or_zero())) * w;
+                    }
+                }
+                // crests are surface particles moving along their normal
+                if normals[i].length() < SURFACE_NORMAL || vel.normalize_or_zero().dot(n) < 0.6 {
+                    wave_crest = 0.0;
+                }
+                let kinetic_energy = 0.5 * vel.length_squared();
+                params.emission(trapped_air, wave_crest, kinetic_energy) * dt
+            })
+            .collect();
+        for (i, &expected) in emission.iter().enumerate() {
+            if expected > 0.0 {
+                diffuse.emit(
+                    self.position[i],
+                    self.velocity[i],
+                    expected,
+                    self.radius,
+                    dt,
+                );
+            }
+        }
+
+        let gravity = self.tank_rotation.inverse() * self.params.gravity();
+        let params = &diffuse.params;
+        diffuse.particles.par_iter_mut().for_each(|p| {
+            let (density, velocity) = self.sample_fluid(p.position);
+            advect(p, params, density, velocity, gravity, dt);
+            p.position = self.wrap(p.position);
+        });
+        let half = 0.5 * self.tank;
+        diffuse.particles.retain(|p| {
+            p.age < params.lifetime(p.kind) && (p.position.abs() - half).max_element() < 0.0
+        });
+        self.diffuse = Some(diffuse);
     }
 
     fn update_particle_colors(&mut self) {
         for i in 0..self.num_sphere {
-            let rel_density = f32::clamp(f32::sqrt(self.neighbor[i].len() as f32 / 13.0), 0.7, 1.0);
+            let rel_density =
+                Real::clamp(Real::sqrt(self.neighbor[i].len() as Real / 13.0), 0.7, 1.0);
             self.color[i].x = 1.0 - rel_density;
             self.color[i].y = 1.0 - (1.0 - 30.0 / 255.0) * rel_density;
         }
     }
 
-    pub fn simulate_timestep(&mut self, dt: f32) {
-        if self.scene_id == 1 {
-            self.slide_pos += self.slide_dir as f32 * self.slide_vel * dt;
-            if self.slide_pos > 1.0 {
-                self.slide_dir = -1;
-                self.slide_pos = 2.0 - self.slide_pos;
-            } else if self.slide_pos < 0.5 {
-                self.slide_dir = 1;
-                self.slide_pos = 1.0 - self.slide_pos;
+    // the phases of one time step in order, the solver iterations repeat the constraint phases
+    pub fn step_phases(&self) -> Vec<Phase> {
+        let mut phases = vec![
+            Phase::Integrate,
+            Phase::Collisions,
+            Phase::BuildHashtable,
+            Phase::DetectNeighbor,
+        ];
+        for _ in 0..self.params.solver_iterations() {
+            phases.extend([Phase::Lambda, Phase::PositionCorrection, Phase::Collisions]);
+        }
+        phases.extend([Phase::VelocityUpdate, Phase::Diffuse]);
+        phases
+    }
+
+    // run one phase of a time step, running all of step_phases in order is one time step
+    pub fn run_phase(&mut self, phase: Phase, dt: Real) {
+        match phase {
+            Phase::Integrate => {
+                self.time += dt;
+                for boundary in &mut self.boundaries {
+                    boundary.update(self.time, dt);
+                }
+                let gravity = self.tank_rotation.inverse() * self.params.gravity();
+                for body in &mut self.rigid_bodies {
+                    body.integrate(dt, gravity);
+                }
+                self.intergrate_particles(dt);
             }
+            Phase::Collisions => self.handle_collisions(),
+            Phase::BuildHashtable => {
+                self.build_boundary_hashtable();
+                self.build_hashtable();
+            }
+            Phase::DetectNeighbor => self.search_neighbors(),
+            Phase::Lambda => self.compute_lambda(),
+            Phase::PositionCorrection => self.correct_positions(),
+            Phase::VelocityUpdate => {
+                self.velocity_update(dt);
+                let half = 0.5 * self.tank;
+                for body in &mut self.rigid_bodies {
+                    body.apply_exchange(dt);
+                    body.collide_tank(half);
+                }
+            }
+            Phase::Diffuse => self.update_diffuse(dt),
         }
-        self.intergrate_particles(dt);
-        self.detect_neighbor();
-        for _ in 0..self.solver_iteration {
-            self.constraint_solve();
+    }
+
+    pub fn simulate_timestep(&mut self, dt: Real) {
+        for phase in self.step_phases() {
+            self.run_phase(phase, dt);
         }
-        self.velocity_update(dt);
         // self.update_particle_colors();
     }
 
     fn setup_scene(&mut self) {
-        let base =
-            -self.tank * 0.5 + self.offset * (vec3(1.0, 1.0, 1.0) - self.rel_water) * self.tank;
-
+        self.radius = self.params.radius();
         let dx = 2.0 * self.radius;
-        let dy = 3.0_f32.sqrt() / 2.0 * dx;
+        let dy = (3.0 as Real).sqrt() / 2.0 * dx;
         let dz = dx;
 
-        let num_x = (self.rel_water.x * self.tank.x / dx).floor() as usize;
-        let num_y = (self.rel_water.y * self.tank.y / dy).floor() as usize;
-        let num_z = (self.rel_water.z * self.tank.z / dz).floor() as usize;
+        // create particles
+        let mut position = Vec::new();
+        let mut material = Vec::new();
+        for block in &self.scene.blocks {
+            let base = -self.tank * 0.5 + block.offset * (Vector::ONE - block.size) * self.tank;
+            let num_x = (block.size.x * self.tank.x / dx).floor() as usize;
+            let num_y = (block.size.y * self.tank.y / dy).floor() as usize;
+            let num_z = (block.size.z * self.tank.z / dz).floor() as usize;
+            for i in 0..num_x {
+                for j in 0..num_y {
+                    for k in 0..num_z {
+                        position.push(
+                            vector(
+                                self.radius
+                                    + dx * i as Real
+                                    + (if j % 2 == 0 { 0.0 } else { self.radius }),
+                                self.radius + dy * j as Real,
+                                self.radius
+                                    + dz * k as Real
+                                    + (if j % 2 == 0 { 0.0 } else { self.radius }),
+                            ) + base,
+                        );
+                    }
+                }
+            }
+            material.resize(position.len(), block.material);
+        }
+        let mut body = vec![None; position.len()];
+
+        // solids are granular particles on a cubic lattice, kept in shape by shape matching
+        let mut solids = Vec::new();
+        for (b, solid) in self.scene.solids.iter().enumerate() {
+            let start = position.len();
+            let count = (solid.half_extents / self.radius).floor().max(Vector::ONE);
+            let first = solid.center - (count - 1.0) * self.radius;
+            for i in 0..count.x as usize {
+                for j in 0..count.y as usize {
+                    for k in 0..count.z as usize {
+                        position.push(first + vector(i as Real, j as Real, k as Real) * dx);
+                    }
+                }
+            }
+            material.resize(
+                position.len(),
+                Material::Granular {
+                    static_friction: solid.friction,
+                    kinetic_friction: solid.friction,
+                },
+            );
+            body.resize(position.len(), Some(b));
+            solids.push((start..position.len(), solid.stiffness));
+        }
+        self.bodies = solids
+            .into_iter()
+            .map(|(particles, stiffness)| ShapeMatching::new(particles, &position, stiffness))
+            .collect();
 
         // update object member attributes
-        self.num_sphere = num_x * num_y * num_z;
-        self.h = self.radius * self.ratio;
-        self.cell_x = (self.tank.x / self.h).ceil() as usize + 2;
-        self.cell_y = (self.tank.y / self.h).ceil() as usize + 2;
-        self.cell_z = (self.tank.z / self.h).ceil() as usize + 2;
+        self.num_sphere = position.len();
+        self.h = self.radius * self.params.ratio();
+        // a whole number of cells along periodic axes, so that the cells at both ends are
+        // neighbors across the seam
+        let cells = Vector::select(
+            self.periodic,
+            (self.tank / self.h).floor().max(Vector::ONE),
+            (self.tank / self.h).ceil(),
+        );
+        self.cell_size = Vector::select(self.periodic, self.tank / cells, Vector::splat(self.h));
+        self.cell_x = cells.x as usize + 2;
+        self.cell_y = cells.y as usize + 2;
+        self.cell_z = cells.z as usize + 2;
         self.num_cell = self.cell_x * self.cell_y * self.cell_z;
 
         // update particle array
-        self.position.clear();
-        self.position.resize(self.num_sphere, Vec3::ZERO);
+        self.position = position;
         self.velocity.clear();
-        self.velocity.resize(self.num_sphere, Vec3::ZERO);
-        self.color.clear();
-        self.color.resize(self.num_sphere, Vec3::new(0.0, 30.0 / 255.0, 1.0));
+        self.velocity.resize(self.num_sphere, Vector::ZERO);
+        self.color = material
+            .iter()
+            .zip(&body)
+            .map(|(material, body)| match body {
+                Some(_) => vector(0.8, 0.25, 0.3),
+                None => material.color(),
+            })
+            .collect();
+        self.material = material;
+        self.body = body;
 
-        self.position_.clear();
-        self.position_.resize(self.num_sphere, Vec3::ZERO);
         self.neighbor.clear();
         self.neighbor.resize(self.num_sphere, Vec::new());
+        self.lambda.clear();
+        self.lambda.resize(self.num_sphere, 0.0);
+        self.contact.clear();
+        self.contact.resize(self.num_sphere, None);
+        self.boundary_neighbor.clear();
+        self.boundary_neighbor.resize(self.num_sphere, Vec::new());
 
         self.hashtable.clear();
         self.hashtable.resize(self.num_sphere, 0);
@@ -430,43 +1260,222 @@ impl Simulator {
         self.hashtableindex.resize(self.num_cell + 1, 0);
 
         // the rest density can be assigned after scene initialization
-        const FACTOR: f32 = INV_PI * 315.0 * 5.0 * 5.0 * 5.0 / (64.0 * 9.0 * 9.0 * 9.0);
+        // poly6 at the rest spacing 2r, times h^3
+        let q = 2.0 / self.params.ratio();
+        let factor = INV_PI * 315.0 / 64.0 * Real::powi(1.0 - q * q, 3);
         let h = self.h;
-        self.rest_density = FACTOR * (self.num as f32) / (h * h * h);
+        self.rest_density = factor * (self.params.rest_neighbors() as Real) / (h * h * h);
 
-        // create particles
-        let mut p = 0;
-        for i in 0..num_x {
-            for j in 0..num_y {
-                for k in 0..num_z {
-                    self.position[p] = vec3(
-                        self.radius + dx * i as f32 + (if j % 2 == 0 { 0.0 } else { self.radius }),
-                        self.radius + dy * j as f32,
-                        self.radius + dz * k as f32 + (if j % 2 == 0 { 0.0 } else { self.radius }),
-                    ) + base;
-                    p += 1;
+        self.position_ = self.position.clone();
+
+        self.setup_boundary_particles();
+    }
+
+    // sample the tank walls and every boundary at the fluid particle spacing
+    fn setup_boundary_particles(&mut self) {
+        let spacing = 2.0 * self.radius;
+        self.boundary_owner.clear();
+        self.boundary_local.clear();
+        self.boundary_volume.clear();
+
+        let (walls, volumes) = self.tank_walls(spacing);
+        self.boundary_volume.extend(volumes);
+        self.boundary_owner
+            .extend(std::iter::repeat_n(SampleOwner::Tank, walls.len()));
+        self.boundary_local.extend(walls);
+        for (b, boundary) in self.boundaries.iter().enumerate() {
+            let samples = boundary.sample_surface(spacing, self.tank);
+            self.boundary_volume
+                .extend(sample_volumes(&samples, self.h));
+            let owner = SampleOwner::Boundary(b);
+            self.boundary_owner
+                .extend(std::iter::repeat_n(owner, samples.len()));
+            self.boundary_local.extend(samples);
+        }
+        for (b, body) in self.rigid_bodies.iter().enumerate() {
+            let samples = body.shape.shape().sample_surface(spacing, self.tank);
+            self.boundary_volume
+                .extend(sample_volumes(&samples, self.h));
+            let owner = SampleOwner::RigidBody(b);
+            self.boundary_owner
+                .extend(std::iter::repeat_n(owner, samples.len()));
+            self.boundary_local.extend(samples);
+        }
+
+        self.boundary_position.clear();
+        self.boundary_position
+            .resize(self.boundary_local.len(), Vector::ZERO);
+        self.boundary_hashtableindex.clear();
+        self.boundary_hashtableindex.resize(self.num_cell + 1, 0);
+        self.build_boundary_hashtable();
+    }
+
+    // samples of the tank walls and their volumes, periodic axes have no walls at their ends
+    // and the samples next to a seam also count the ones on the other side
+    fn tank_walls(&self, spacing: Real) -> (Vec<Vector>, Vec<Real>) {
+        let half = 0.5 * self.tank;
+        let walls = box_surface(half, spacing);
+        if !self.periodic.any() {
+            let volumes = sample_volumes(&walls, self.h);
+            return (walls, volumes);
+        }
+
+        let tolerance = 0.25 * spacing;
+        let periodic = |axis: usize| self.periodic.test(axis);
+        let walls: Vec<Vector> = walls
+            .into_iter()
+            .filter(|&p| {
+                // on a wall, and only once where the low and high end are the same place
+                (0..3).any(|a| !periodic(a) && p[a].abs() > half[a] - tolerance)
+                    && (0..3).all(|a| !periodic(a) || p[a] < half[a] - tolerance)
+            })
+            .collect();
+
+        let shifts = |axis: usize| {
+            if periodic(axis) {
+                vec![-1.0, 0.0, 1.0]
+            } else {
+                vec![0.0]
+            }
+        };
+        let mut extended = walls.clone();
+        for x in shifts(0) {
+            for y in shifts(1) {
+                for z in shifts(2) {
+                    let offset = vector(x, y, z) * self.tank;
+                    if offset == Vector::ZERO {
+                        continue;
+                    }
+                    extended.extend(
+                        walls
+                            .iter()
+                            .map(|&p| p + offset)
+                            .filter(|p| (p.abs() - half).max_element() < self.h),
+                    );
                 }
             }
         }
-
-        self.position_ = self.position.clone();
+        let mut volumes = sample_volumes(&extended, self.h);
+        volumes.truncate(walls.len());
+        (walls, volumes)
     }
 
     pub fn reset_system(&mut self) {
         if self.scene_changed {
-            if self.scene_id == 0 {
-                self.tank = vec3(1.0, 2.0, 1.0);
-                self.rel_water = vec3(0.8, 0.8, 0.3);
-                self.offset = vec3(0.5, 1.0, 0.7);
-            } else if self.scene_id == 1 {
-                self.tank = vec3(2.0, 1.0, 0.5);
-                self.rel_water = vec3(0.4, 0.6, 1.0);
-                self.offset = vec3(0.0, 0.0, 0.5);
-            }
-            self.slide_pos = 1.0;
-            self.slide_vel = 1.0;
-            self.slide_dir = -1;
+            self.tank = self.scene.tank;
+            self.tank_surface = self.scene.tank_surface;
+            self.periodic = self.scene.periodic;
+            self.boundaries = self.scene.boundaries.clone();
+            self.force_fields = self.scene.force_fields.clone();
+        }
+        self.rigid_bodies = self.scene.rigid_bodies.clone();
+        self.diffuse = self.scene.diffuse.clone().map(DiffuseSystem::new);
+        self.time = 0.0;
+        for boundary in &mut self.boundaries {
+            boundary.reset();
         }
         self.setup_scene();
     }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            scene: self.scene.clone(),
+            state: self.state(),
+        }
+    }
+
+    // the current state of the scene, without the scene itself
+    pub fn state(&self) -> SimState {
+        SimState {
+            params: self.params.clone(),
+            time: self.time,
+            tank_rotation: self.tank_rotation,
+            position: self.position.clone(),
+            velocity: self.velocity.clone(),
+            rigid_bodies: self
+                .rigid_bodies
+                .iter()
+                .map(|body| BodyState {
+                    position: body.position,
+                    rotation: body.rotation,
+                    velocity: body.velocity,
+                    angular_velocity: body.angular_velocity,
+                })
+                .collect(),
+            solids: self.bodies.iter().map(ShapeMatching::rotation).collect(),
+            diffuse: self.diffuse.clone(),
+        }
+    }
+
+    // set the checkpoint's scene up and continue from the saved state
+    // stepping on with the saved time step repeats the original run
+    pub fn restore(&mut self, checkpoint: Checkpoint) -> Result<(), CheckpointError> {
+        let params = checkpoint
+            .state
+            .params
+            .to_builder()
+            .build()
+            .map_err(CheckpointError::Params)?;
+        let mut restored = self.clone();
+        restored.params = params;
+        restored.set_scene(checkpoint.scene);
+        restored.reset_system();
+        restored.set_state(&checkpoint.state)?;
+        *self = restored;
+        Ok(())
+    }
+
+    // move the current scene to a saved state of it, without setting it up again
+    // the simulator is left untouched if the state does not fit the scene
+    pub fn set_state(&mut self, state: &SimState) -> Result<(), CheckpointError> {
+        let params = state
+            .params
+            .to_builder()
+            .build()
+            .map_err(CheckpointError::Params)?;
+        let counts = [
+            ("particles", state.position.len(), self.num_sphere),
+            ("velocities", state.velocity.len(), self.num_sphere),
+            (
+                "rigid bodies",
+                state.rigid_bodies.len(),
+                self.rigid_bodies.len(),
+            ),
+            ("solids", state.solids.len(), self.bodies.len()),
+        ];
+        for (what, saved, scene) in counts {
+            if saved != scene {
+                return Err(CheckpointError::Mismatch { what, saved, scene });
+            }
+        }
+
+        self.params = params;
+        self.time = state.time;
+        self.tank_rotation = state.tank_rotation;
+        self.position.clone_from(&state.position);
+        self.velocity.clone_from(&state.velocity);
+        for (body, state) in self.rigid_bodies.iter_mut().zip(&state.rigid_bodies) {
+            body.position = state.position;
+            body.rotation = state.rotation;
+            body.velocity = state.velocity;
+            body.angular_velocity = state.angular_velocity;
+        }
+        for (body, &rotation) in self.bodies.iter_mut().zip(&state.solids) {
+            body.set_rotation(rotation);
+        }
+        self.diffuse.clone_from(&state.diffuse);
+        // moving boundaries take their velocity from the pose of the step before
+        let dt = self.params.time_step();
+        for boundary in &mut self.boundaries {
+            if self.time > 0.0 {
+                boundary.update(self.time - dt, dt);
+                boundary.update(self.time, dt);
+            } else {
+                boundary.reset();
+            }
+        }
+        // neighbor lists of the saved positions, for the densities before the first step
+        self.find_neighbors();
+        Ok(())
+    }
 }
@@ -0,0 +1,164 @@
+use bevy::{color::palettes::basic::GRAY, prelude::*};
+
+use pbf_rs::precision::{Real, to_f32, to_vec3};
+use pbf_rs::simulator::{Phase, Simulator};
+
+use crate::cache_playback::CachePlayback;
+use crate::scene::SimRunning;
+use crate::timeline::Timeline;
+
+const PREDICTED_COLOR: Color = Color::srgb(1.0, 0.6, 0.0);
+
+// a time step run one phase at a time
+#[derive(Resource, Default)]
+pub struct Stepper {
+    step: Option<PartialStep>,
+}
+
+struct PartialStep {
+    phases: Vec<Phase>,
+    next: usize, // index into phases
+    dt: Real,
+    time: Real, // simulated time after the last phase run
+}
+
+impl Stepper {
+    // the simulator is in the middle of a step, its predicted positions are not committed yet
+    pub fn is_mid_step(&self) -> bool {
+        self.step.is_some()
+    }
+
+    fn run_phase(&mut self, simulator: &mut Simulator) {
+        let step = self.step.get_or_insert_with(|| PartialStep {
+            phases: simulator.step_phases(),
+            next: 0,
+            dt: simulator.params().time_step(),
+            time: simulator.time,
+        });
+        simulator.run_phase(step.phases[step.next], step.dt);
+        step.next += 1;
+        step.time = simulator.time;
+        if step.next == step.phases.len() {
+            self.step = None;
+        }
+    }
+
+    // run the rest of the step in progress
+    pub fn finish(&mut self, simulator: &mut Simulator) {
+        while self.is_mid_step() {
+            self.run_phase(simulator);
+        }
+    }
+
+    // how far the step is, e.g. "7 of 13 phases run, next lambda, iteration 2 of 3"
+    fn describe(&self) -> String {
+        let Some(step) = &self.step else {
+            return String::new();
+        };
+        let lambdas = |phases: &[Phase]| phases.iter().filter(|&&p| p == Phase::Lambda).count();
+        let iterations = lambdas(&step.phases);
+        let done = lambdas(&step.phases[..step.next]);
+        let next = step.phases[step.next];
+        let iteration = match next {
+            Phase::Lambda => done + 1,
+            Phase::PositionCorrection => done,
+            Phase::Collisions if done > 0 => done,
+            _ => 0,
+        };
+        let mut text = format!(
+            "{} of {} phases run, next {}",
+            step.next,
+            step.phases.len(),
+            next.name()
+        );
+        if iteration > 0 {
+            text += &format!(", iteration {iteration} of {iterations}");
+        }
+        text
+    }
+}
+
+#[derive(Component)]
+pub struct StepperText;
+
+pub fn setup_stepper_text(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.spawn((
+        StepperText,
+        Text::new(""),
+        TextFont {
+            font: assets.load("fonts/FiraSans-Bold.ttf"),
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(PREDICTED_COLOR),
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(20.0),
+            bottom: Val::Px(100.0),
+            ..default()
+        },
+    ));
+}
+
+// N stops the simulation and runs one time step, or the rest of the step in progress
+// shift + N stops it and runs the next phase of the step
+pub fn stepping_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut sim_running: ResMut<SimRunning>,
+    mut stepper: ResMut<Stepper>,
+    mut simulator: ResMut<Simulator>,
+    timeline: Res<Timeline>,
+    cache: Option<Res<CachePlayback>>,
+    mut text_query: Query<&mut Text, With<StepperText>>,
+) {
+    // a reset, another scene or a recorded frame replaced the state the step was working on
+    if let Some(step) = &stepper.step
+        && simulator.time != step.time
+    {
+        stepper.step = None;
+    }
+
+    if keys.just_pressed(KeyCode::KeyN) && !timeline.is_playing_back() && cache.is_none() {
+        sim_running.0 = false;
+        if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            stepper.run_phase(&mut simulator);
+        } else if stepper.is_mid_step() {
+            stepper.finish(&mut simulator);
+        } else {
+            let dt = simulator.params().time_step();
+            simulator.simulate_timestep(dt);
+        }
+    }
+
+    let status = stepper.describe();
+    for mut text in &mut text_query {
+        if **text != status {
+            **text = status.clone();
+        }
+    }
+}
+
+// during a step the predicted positions are drawn as orange spheres, joined to the
+// committed positions the particles are still shown at
+pub fn stepping_gizmo_system(mut gizmos: Gizmos, stepper: Res<Stepper>, simulator: Res<Simulator>) {
+    if !stepper.is_mid_step() {
+        return;
+    }
+    let radius = to_f32(simulator.radius);
+    for (&committed, &predicted) in simulator
+        .position
+        .iter()
+        .zip(simulator.predicted_position())
+    {
+        let committed = to_vec3(simulator.to_world(committed));
+        let predicted = to_vec3(simulator.to_world(predicted));
+        gizmos.line(committed, predicted, GRAY);
+        gizmos
+            .sphere(
+                Isometry3d::from_translation(predicted),
+                radius,
+                PREDICTED_COLOR,
+            )
+            .resolution(6);
+    }
+}
@@ -0,0 +1,111 @@
+use bevy::{color::palettes::basic::*, input::mouse::AccumulatedMouseMotion, prelude::*};
+
+use pbf_rs::precision::{Rotation, Vector, from_quat, to_vec3};
+use pbf_rs::simulator::Simulator;
+
+const KEY_TILT_SPEED: f32 = 0.8; // radians per second
+const DRAG_TILT_SPEED: f32 = 0.005; // radians per pixel
+
+// what the tilt controls rotate
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum TiltTarget {
+    #[default]
+    Tank,
+    Gravity,
+}
+
+#[derive(Resource, Default)]
+pub struct TiltControl {
+    pub target: TiltTarget,
+}
+
+// I/K and J/L or ctrl + left drag tilt the tank or the gravity vector,
+// G switches between the two and O levels everything again
+pub fn tilt_control_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mouse_motion: Res<AccumulatedMouseMotion>,
+    time: Res<Time>,
+    camera: Query<&Transform, With<Camera3d>>,
+    mut control: ResMut<TiltControl>,
+    mut simulator: ResMut<Simulator>,
+) {
+    if keys.just_pressed(KeyCode::KeyG) {
+        control.target = match control.target {
+            TiltTarget::Tank => TiltTarget::Gravity,
+            TiltTarget::Gravity => TiltTarget::Tank,
+        };
+    }
+
+    if keys.just_pressed(KeyCode::KeyO) {
+        simulator.set_tank_rotation(Rotation::IDENTITY);
+        let gravity = simulator.params().gravity();
+        let level = Vector::new(0.0, -gravity.length(), 0.0);
+        if let Ok(params) = simulator.params().to_builder().gravity(level).build() {
+            simulator.set_params(params);
+        }
+        return;
+    }
+
+    // pitch turns about the camera right axis, roll about the viewing direction
+    let step = KEY_TILT_SPEED * time.delta_secs();
+    let mut pitch = 0.0;
+    let mut roll = 0.0;
+    if keys.pressed(KeyCode::KeyI) {
+        pitch -= step;
+    }
+    if keys.pressed(KeyCode::KeyK) {
+        pitch += step;
+    }
+    if keys.pressed(KeyCode::KeyJ) {
+        roll -= step;
+    }
+    if keys.pressed(KeyCode::KeyL) {
+        roll += step;
+    }
+    if keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+        && mouse.pressed(MouseButton::Left)
+    {
+        pitch += mouse_motion.delta.y * DRAG_TILT_SPEED;
+        roll += mouse_motion.delta.x * DRAG_TILT_SPEED;
+    }
+    if pitch == 0.0 && roll == 0.0 {
+        return;
+    }
+
+    let Ok(camera) = camera.single() else {
+        return;
+    };
+    let delta = from_quat(
+        Quat::from_axis_angle(camera.right().as_vec3(), pitch)
+            * Quat::from_axis_angle(camera.forward().as_vec3(), roll),
+    );
+    match control.target {
+        TiltTarget::Tank => {
+            let rotation = delta * simulator.tank_rotation();
+            simulator.set_tank_rotation(rotation);
+        }
+        TiltTarget::Gravity => {
+            let gravity = delta * simulator.params().gravity();
+            if let Ok(params) = simulator.params().to_builder().gravity(gravity).build() {
+                simulator.set_params(params);
+            }
+        }
+    }
+}
+
+// an arrow from the tank center along gravity, highlighted while it is being tilted
+pub fn gravity_gizmo_system(
+    mut gizmos: Gizmos,
+    control: Res<TiltControl>,
+    simulator: Res<Simulator>,
+) {
+    let Some(dir) = to_vec3(simulator.params().gravity()).try_normalize() else {
+        return;
+    };
+    let color = match control.target {
+        TiltTarget::Tank => GRAY,
+        TiltTarget::Gravity => YELLOW,
+    };
+    gizmos.arrow(Vec3::ZERO, dir * 0.3, color);
+}
@@ -0,0 +1,379 @@
+use bevy::{prelude::*, ui::RelativeCursorPosition};
+
+use pbf_rs::checkpoint::CheckpointError;
+use pbf_rs::precision::{Real, to_f32};
+use pbf_rs::recording::Recording;
+use pbf_rs::simulator::Simulator;
+
+use crate::cache_playback::CachePlayback;
+use crate::scene::{HOVERED_BUTTON, NORMAL_BUTTON};
+use crate::stepping::Stepper;
+
+const RECORDING_BUDGET: usize = 512 << 20; // bytes of simulation states kept for playback
+const SPEEDS: [Real; 5] = [0.1, 0.25, 0.5, 1.0, 2.0];
+const TRACK_COLOR: Color = Color::srgb(0.2, 0.2, 0.2);
+const FILL_COLOR: Color = Color::srgb(0.0, 30.0 / 255.0, 1.0);
+
+// every state the live simulation went through, and the one shown while playing them back
+#[derive(Resource)]
+pub struct Timeline {
+    recording: Recording,
+    playback: Option<Playback>,
+    speed: usize, // index into SPEEDS
+}
+
+struct Playback {
+    time: Real, // simulated time shown
+    playing: bool,
+    shown: Option<usize>, // frame the simulator holds
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            recording: Recording::new(RECORDING_BUDGET),
+            playback: None,
+            speed: 3,
+        }
+    }
+}
+
+impl Timeline {
+    // the simulator shows a recorded frame and must not step
+    pub fn is_playing_back(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    fn speed(&self) -> Real {
+        SPEEDS[self.speed]
+    }
+
+    // switch from the live simulation to the recording
+    fn enter_playback(&mut self, time: Real, playing: bool) {
+        self.playback = Some(Playback {
+            time,
+            playing,
+            shown: None,
+        });
+    }
+
+    // play on by the given simulated time and put the frame at the playback time in the simulator
+    fn advance(&mut self, delta: Real, simulator: &mut Simulator) -> Result<(), CheckpointError> {
+        let Some(playback) = &mut self.playback else {
+            return Ok(());
+        };
+        let end = self.recording.end_time().unwrap_or(0.0);
+        if playback.playing {
+            playback.time += delta;
+            if playback.time >= end {
+                playback.time = end;
+                playback.playing = false;
+            }
+        }
+        let index = self.recording.index_at(playback.time);
+        if index != playback.shown
+            && let Some(frame) = index.and_then(|i| self.recording.get(i))
+        {
+            simulator.set_state(frame)?;
+            playback.shown = index;
+        }
+        Ok(())
+    }
+
+    // move by whole frames, from the frame shown or the end of the recording
+    fn step(&mut self, frames: isize) {
+        let last = self.recording.len().saturating_sub(1);
+        let current = match &self.playback {
+            Some(playback) => self.recording.index_at(playback.time).unwrap_or(0),
+            None => last,
+        };
+        let index = current.saturating_add_signed(frames).min(last);
+        if let Some(frame) = self.recording.get(index) {
+            self.enter_playback(frame.time, false);
+        }
+    }
+}
+
+// what a timeline button does
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TimelineButton {
+    StepBack,
+    PlayPause,
+    StepForward,
+    Speed,
+    Resume,
+}
+
+#[derive(Component)]
+pub struct TimelineTrack;
+
+#[derive(Component)]
+pub struct TimelineFill;
+
+#[derive(Component)]
+pub struct TimelineLabel;
+
+fn timeline_button(action: TimelineButton, width: f32, font: &Handle<Font>) -> impl Bundle {
+    (
+        Button,
+        action,
+        Node {
+            width: Val::Px(width),
+            height: Val::Px(32.0),
+            border: UiRect::all(Val::Px(2.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        BorderColor(Color::BLACK),
+        BorderRadius::all(Val::Px(6.0)),
+        BackgroundColor(NORMAL_BUTTON),
+        children![(
+            Text::new(""),
+            TextFont {
+                font: font.clone(),
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        )],
+    )
+}
+
+pub fn setup_timeline(mut commands: Commands, assets: Res<AssetServer>) {
+    let font: Handle<Font> = assets.load("fonts/FiraSans-Bold.ttf");
+    commands.spawn((
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(20.0),
+            right: Val::Px(20.0),
+            bottom: Val::Px(50.0),
+            align_items: AlignItems::Center,
+            column_gap: Val::Px(8.0),
+            ..default()
+        },
+        children![
+            timeline_button(TimelineButton::StepBack, 32.0, &font),
+            timeline_button(TimelineButton::PlayPause, 80.0, &font),
+            timeline_button(TimelineButton::StepForward, 32.0, &font),
+            timeline_button(TimelineButton::Speed, 56.0, &font),
+            timeline_button(TimelineButton::Resume, 120.0, &font),
+            (
+                Button,
+                TimelineTrack,
+                RelativeCursorPosition::default(),
+                Node {
+                    flex_grow: 1.0,
+                    height: Val::Px(14.0),
+                    ..default()
+                },
+                BorderRadius::all(Val::Px(4.0)),
+                BackgroundColor(TRACK_COLOR),
+                children![(
+                    TimelineFill,
+                    Node {
+                        width: Val::Percent(0.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    BorderRadius::all(Val::Px(4.0)),
+                    BackgroundColor(FILL_COLOR),
+                )]
+            ),
+            (
+                TimelineLabel,
+                Text::new(""),
+                TextFont {
+                    font,
+                    font_size: 16.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            )
+        ],
+    ));
+}
+
+// keep every new state of the live simulation, a reset or another scene starts over
+pub fn record_system(
+    simulator: Res<Simulator>,
+    stepper: Res<Stepper>,
+    cache: Option<Res<CachePlayback>>,
+    mut timeline: ResMut<Timeline>,
+) {
+    // only whole steps of the live simulation are recorded
+    if timeline.is_playing_back() || stepper.is_mid_step() || cache.is_some() {
+        return;
+    }
+    if let Some(last) = timeline.recording.last() {
+        let same_scene = last.position.len() == simulator.num_sphere;
+        // nothing new while the simulation is stopped
+        if same_scene && last.time == simulator.time {
+            return;
+        }
+        if !same_scene || simulator.time < last.time {
+            timeline.recording.clear();
+        }
+    }
+    if timeline.recording.is_empty() {
+        timeline.recording.start(simulator.scene().clone());
+    }
+    timeline.recording.push(simulator.state());
+}
+
+// space plays and pauses the recording, comma and period step through it frame by frame,
+// enter resumes the live simulation from the frame shown
+// the buttons do the same, and dragging on the track scrubs
+#[allow(clippy::type_complexity)]
+pub fn timeline_control_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    mut button_query: Query<
+        (
+            &Interaction,
+            &TimelineButton,
+            &mut BackgroundColor,
+            &mut BorderColor,
+        ),
+        Changed<Interaction>,
+    >,
+    track_query: Query<(&Interaction, &RelativeCursorPosition), With<TimelineTrack>>,
+    mut timeline: ResMut<Timeline>,
+    mut simulator: ResMut<Simulator>,
+) {
+    let mut actions = Vec::new();
+    for (key, action) in [
+        (KeyCode::Space, TimelineButton::PlayPause),
+        (KeyCode::Comma, TimelineButton::StepBack),
+        (KeyCode::Period, TimelineButton::StepForward),
+        (KeyCode::Enter, TimelineButton::Resume),
+    ] {
+        if keys.just_pressed(key) {
+            actions.push(action);
+        }
+    }
+    for (interaction, action, mut color, mut border_color) in &mut button_query {
+        match *interaction {
+            Interaction::Pressed => {
+                actions.push(*action);
+                border_color.0 = Color::WHITE;
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON.into();
+                border_color.0 = Color::WHITE;
+            }
+            Interaction::None => {
+                *color = NORMAL_BUTTON.into();
+                border_color.0 = Color::BLACK;
+            }
+        }
+    }
+
+    let (Some(start), Some(end)) = (
+        timeline.recording.start_time(),
+        timeline.recording.end_time(),
+    ) else {
+        return;
+    };
+    for action in actions {
+        match action {
+            TimelineButton::StepBack => timeline.step(-1),
+            TimelineButton::StepForward => timeline.step(1),
+            TimelineButton::PlayPause => match &mut timeline.playback {
+                // from the end a replay starts over
+                Some(playback) if !playback.playing && playback.time >= end => {
+                    playback.time = start;
+                    playback.playing = true;
+                }
+                Some(playback) => playback.playing = !playback.playing,
+                None => timeline.enter_playback(start, true),
+            },
+            TimelineButton::Speed => timeline.speed = (timeline.speed + 1) % SPEEDS.len(),
+            TimelineButton::Resume => {
+                // the simulator holds the frame shown, the frames after it are a discarded future
+                if let Some(playback) = timeline.playback.take()
+                    && let Some(shown) = playback.shown
+                {
+                    timeline.recording.truncate(shown + 1);
+                }
+            }
+        }
+    }
+
+    for (interaction, cursor) in &track_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some(normalized) = cursor.normalized else {
+            continue;
+        };
+        let t = start + (end - start) * (normalized.x as Real).clamp(0.0, 1.0);
+        match &mut timeline.playback {
+            Some(playback) => playback.time = t,
+            None => timeline.enter_playback(t, false),
+        }
+    }
+
+    let delta = timeline.speed() * time.delta_secs() as Real;
+    if let Err(err) = timeline.advance(delta, &mut simulator) {
+        warn!("failed to show the recorded frame: {err}");
+        timeline.recording.clear();
+        timeline.playback = None;
+    }
+}
+
+pub fn timeline_refresh_system(
+    simulator: Res<Simulator>,
+    timeline: Res<Timeline>,
+    button_query: Query<(&TimelineButton, &Children)>,
+    mut text_query: Query<&mut Text, Without<TimelineLabel>>,
+    mut fill_query: Query<&mut Node, With<TimelineFill>>,
+    mut label_query: Query<&mut Text, With<TimelineLabel>>,
+) {
+    if !timeline.is_changed() && !simulator.is_changed() {
+        return;
+    }
+    let playing = timeline.playback.as_ref().is_some_and(|p| p.playing);
+    for (action, children) in &button_query {
+        let Ok(mut text) = text_query.get_mut(children[0]) else {
+            continue;
+        };
+        let label = match action {
+            TimelineButton::StepBack => "<".to_string(),
+            TimelineButton::StepForward => ">".to_string(),
+            TimelineButton::PlayPause if !timeline.is_playing_back() => "Replay".to_string(),
+            TimelineButton::PlayPause if playing => "Pause".to_string(),
+            TimelineButton::PlayPause => "Play".to_string(),
+            TimelineButton::Speed => format!("{}x", timeline.speed()),
+            TimelineButton::Resume if timeline.is_playing_back() => "Resume Here".to_string(),
+            TimelineButton::Resume => "Live".to_string(),
+        };
+        if **text != label {
+            **text = label;
+        }
+    }
+
+    let recording = &timeline.recording;
+    let (start, end) = (
+        recording.start_time().unwrap_or(0.0),
+        recording.end_time().unwrap_or(0.0),
+    );
+    let shown = timeline.playback.as_ref().map_or(end, |p| p.time);
+    let t = if end > start {
+        (shown - start) / (end - start)
+    } else {
+        1.0
+    };
+    for mut node in &mut fill_query {
+        node.width = Val::Percent(100.0 * to_f32(t));
+    }
+    for mut text in &mut label_query {
+        **text = format!(
+            "{:.2} / {:.2} s  {} frames  {} MB",
+            shown,
+            end,
+            recording.len(),
+            recording.bytes() >> 20
+        );
+    }
+}
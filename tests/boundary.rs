@@ -0,0 +1,45 @@
+use pbf_rs::precision::{Real, Vector, vector};
+
+use crate::common::{block_scene, fluid_block, run};
+
+mod common;
+
+#[test]
+fn density_is_uniform_up_to_the_walls() {
+    let radius = 0.02;
+    let block = fluid_block(vector(1.0, 0.6, 1.0), Vector::ZERO);
+    let mut simulator = block_scene(radius, vector(0.4, 0.5, 0.4), block);
+    run(&mut simulator, 1.5);
+
+    // particles at half the depth of the pool, the fluid is compressed more the deeper it is
+    let floor = -0.5 * simulator.tank.y;
+    let middle = floor + 0.5 * 0.6 * simulator.tank.y;
+    let half = 0.5 * simulator.tank;
+    let h = simulator.neighbor_radius();
+    let density = simulator.relative_density();
+    let layer = |near: bool| {
+        let wall = |p: &Vector| (half.x - p.x.abs()).min(half.z - p.z.abs());
+        let values: Vec<Real> = simulator
+            .position
+            .iter()
+            .zip(&density)
+            .filter(|(p, _)| (p.y - middle).abs() < 0.05)
+            .filter(|(p, _)| {
+                if near {
+                    wall(p) < 2.0 * radius
+                } else {
+                    wall(p) > h
+                }
+            })
+            .map(|(_, d)| *d)
+            .collect();
+        assert!(values.len() > 20);
+        values.iter().sum::<Real>() / values.len() as Real
+    };
+    // the wall samples stand in for the missing fluid neighbors
+    let (at_wall, inside) = (layer(true), layer(false));
+    assert!(
+        (at_wall - inside).abs() < 0.02 * inside,
+        "density {at_wall} at the walls, {inside} inside"
+    );
+}
@@ -0,0 +1,174 @@
+use std::fs;
+use std::path::PathBuf;
+
+use pbf_rs::cache::{CacheError, CacheOptions, CacheReader, CacheWriter};
+use pbf_rs::precision::{Real, Vector, vector};
+use pbf_rs::simulator::Simulator;
+
+use crate::common::{block_scene, fluid_block};
+
+mod common;
+
+const OPTIONS: CacheOptions = CacheOptions {
+    bits: 16,
+    keyframe_interval: 8,
+};
+
+fn path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("pbf_cache_{name}_{}.pbfc", std::process::id()))
+}
+
+// the positions and times of a dam break, every step
+fn dam_break(steps: usize) -> (Simulator, Vec<(Real, Vec<Vector>)>) {
+    let block = fluid_block(vector(0.4, 0.8, 1.0), Vector::ZERO);
+    let mut simulator = block_scene(0.02, vector(0.8, 0.6, 0.3), block);
+    let dt = simulator.params().time_step();
+    let mut frames = vec![(simulator.time, simulator.position.clone())];
+    for _ in 0..steps {
+        simulator.simulate_timestep(dt);
+        frames.push((simulator.time, simulator.position.clone()));
+    }
+    (simulator, frames)
+}
+
+#[test]
+fn frames_round_trip_within_the_quantization_error() {
+    let (simulator, frames) = dam_break(60);
+    let half = 0.5 * simulator.tank + simulator.radius;
+    let path = path("round_trip");
+    let mut writer =
+        CacheWriter::create(&path, -half, half, simulator.num_sphere, OPTIONS).unwrap();
+    for (time, positions) in &frames {
+        writer.write_frame(*time, positions).unwrap();
+    }
+    writer.finish().unwrap();
+
+    let reader = CacheReader::open(&path).unwrap();
+    assert_eq!(reader.len(), frames.len());
+    assert_eq!(reader.particles(), simulator.num_sphere);
+    assert_eq!(reader.bounds(), (-half, half));
+    // a 16 bit grid over the tank
+    let error = reader.max_error();
+    assert!(error.cmple(2.0 * half / 65535.0).all(), "{error}");
+
+    let tolerance = error + Vector::splat(1e-6);
+    let check = |decoded: &[Vector], written: &[Vector]| {
+        assert_eq!(decoded.len(), written.len());
+        for (a, b) in decoded.iter().zip(written) {
+            assert!((*a - *b).abs().cmple(tolerance).all(), "{a} {b}");
+        }
+    };
+    for (decoded, (time, written)) in reader.frames().zip(&frames) {
+        let (decoded_time, decoded) = decoded.unwrap();
+        assert_eq!(decoded_time, *time);
+        check(&decoded, written);
+    }
+    // random access, keyframes and the deltas after them
+    for frame in [60, 0, 33, 7, 8, 9, 59, 16] {
+        assert_eq!(reader.time(frame), Some(frames[frame].0));
+        check(&reader.frame(frame).unwrap(), &frames[frame].1);
+    }
+    assert!(matches!(
+        reader.frame(61),
+        Err(CacheError::FrameOutOfRange { frame: 61, .. })
+    ));
+
+    // far smaller than the raw single precision positions
+    let raw = frames.len() * simulator.num_sphere * 3 * 4;
+    let size = fs::metadata(&path).unwrap().len() as usize;
+    assert!(size * 2 < raw, "{size} bytes for {raw} raw");
+    drop(reader);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn positions_outside_the_bounds_are_clamped() {
+    let path = path("clamped");
+    let min = vector(-1.0, -1.0, -1.0);
+    let mut writer = CacheWriter::create(&path, min, -min, 2, OPTIONS).unwrap();
+    writer
+        .write_frame(0.0, &[vector(5.0, 0.0, -3.0), vector(0.5, 0.25, -0.5)])
+        .unwrap();
+    let err = writer.write_frame(0.1, &[Vector::ZERO]).unwrap_err();
+    assert!(matches!(
+        err,
+        CacheError::ParticleCount {
+            expected: 2,
+            found: 1
+        }
+    ));
+    writer.finish().unwrap();
+
+    let reader = CacheReader::open(&path).unwrap();
+    assert_eq!(reader.len(), 1);
+    let frame = reader.frame(0).unwrap();
+    let tolerance = reader.max_error() + Vector::splat(1e-6);
+    assert!(
+        (frame[0] - vector(1.0, 0.0, -1.0))
+            .abs()
+            .cmple(tolerance)
+            .all()
+    );
+    assert!(
+        (frame[1] - vector(0.5, 0.25, -0.5))
+            .abs()
+            .cmple(tolerance)
+            .all()
+    );
+    drop(reader);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn unfinished_and_invalid_files_are_rejected() {
+    let path = path("unfinished");
+    let bounds = Vector::ONE;
+    let mut writer = CacheWriter::create(&path, -bounds, bounds, 1, OPTIONS).unwrap();
+    writer.write_frame(0.0, &[Vector::ZERO]).unwrap();
+    // dropped without the index
+    drop(writer);
+    assert!(matches!(
+        CacheReader::open(&path),
+        Err(CacheError::Corrupt(_))
+    ));
+
+    fs::write(&path, b"not a cache file at all, just some text").unwrap();
+    assert!(matches!(
+        CacheReader::open(&path),
+        Err(CacheError::Corrupt(_))
+    ));
+    fs::remove_file(&path).unwrap();
+
+    let options = CacheOptions { bits: 0, ..OPTIONS };
+    assert!(matches!(
+        CacheWriter::new(Vec::new(), -bounds, bounds, 1, options),
+        Err(CacheError::Options(_))
+    ));
+    assert!(matches!(
+        CacheWriter::new(Vec::new(), bounds, bounds, 1, OPTIONS),
+        Err(CacheError::Options(_))
+    ));
+}
+
+#[test]
+fn particle_counts_larger_than_the_frames_are_rejected() {
+    let bounds = Vector::ONE;
+    let mut writer = CacheWriter::new(Vec::new(), -bounds, bounds, 2, OPTIONS).unwrap();
+    writer.write_frame(0.0, &[Vector::ZERO; 2]).unwrap();
+    writer.write_frame(0.1, &[Vector::ZERO; 2]).unwrap();
+    let mut data = writer.finish().unwrap();
+    // the particle count in the header
+    let path = path("huge_count");
+    for particles in [u64::MAX, 1 << 40, 3] {
+        data[12..20].copy_from_slice(&particles.to_le_bytes());
+        fs::write(&path, &data).unwrap();
+        assert!(matches!(
+            CacheReader::open(&path),
+            Err(CacheError::Corrupt(_))
+        ));
+    }
+    data[12..20].copy_from_slice(&2u64.to_le_bytes());
+    fs::write(&path, &data).unwrap();
+    assert_eq!(CacheReader::open(&path).unwrap().frame(1).unwrap().len(), 2);
+    fs::remove_file(&path).unwrap();
+}
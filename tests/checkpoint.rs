@@ -0,0 +1,122 @@
+use pbf_rs::boundary::{Boundary, Shape};
+use pbf_rs::checkpoint::{Checkpoint, CheckpointError};
+use pbf_rs::diffuse::DiffuseParams;
+use pbf_rs::motion::{Motion, Track};
+use pbf_rs::params::SimParams;
+use pbf_rs::precision::{Rotation, Vector, vector};
+use pbf_rs::rigid_body::{BodyShape, RigidBody};
+use pbf_rs::scene_desc::{SceneDesc, SolidBlock};
+use pbf_rs::simulator::Simulator;
+
+use crate::common::{bits, fluid_block, run_steps};
+
+mod common;
+
+// everything a checkpoint has to carry: fluid, a solid, a floating box, a moving collider
+// and diffuse particles
+fn scene() -> SceneDesc {
+    SceneDesc {
+        name: "Checkpoint".to_string(),
+        tank: vector(0.8, 0.8, 0.4),
+        blocks: vec![fluid_block(vector(0.5, 0.6, 1.0), vector(0.0, 0.3, 0.5))],
+        solids: vec![SolidBlock {
+            center: vector(0.2, 0.2, 0.0),
+            half_extents: Vector::splat(0.06),
+            stiffness: 0.5,
+            friction: 0.5,
+        }],
+        rigid_bodies: vec![RigidBody::new(
+            BodyShape::Box {
+                half_extents: vector(0.08, 0.04, 0.08),
+            },
+            500.0,
+            vector(-0.2, 0.3, 0.0),
+            Rotation::IDENTITY,
+        )],
+        boundaries: vec![Boundary::new(
+            Shape::Sphere { radius: 0.06 },
+            Motion {
+                translation: Track::Sinusoid {
+                    offset: vector(0.0, -0.2, 0.0),
+                    amplitude: vector(0.2, 0.0, 0.0),
+                    frequency: 1.0,
+                    phase: 0.0,
+                },
+                rotation: Track::default(),
+            },
+        )],
+        diffuse: Some(DiffuseParams::default()),
+        ..SceneDesc::default()
+    }
+}
+
+fn simulator() -> Simulator {
+    common::simulator(0.02, scene())
+}
+
+#[test]
+fn resuming_from_a_checkpoint_repeats_the_run() {
+    let mut original = simulator();
+    run_steps(&mut original, 60);
+    // through the file, so the text format has to keep every bit
+    let path = std::env::temp_dir().join(format!("pbf_checkpoint_{}.ron", std::process::id()));
+    original.checkpoint().save(&path).unwrap();
+    let checkpoint = Checkpoint::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let mut resumed = Simulator::new(SimParams::default());
+    resumed.restore(checkpoint).unwrap();
+    assert_eq!(resumed.params(), original.params());
+    run_steps(&mut original, 60);
+    run_steps(&mut resumed, 60);
+
+    assert_eq!(resumed.time.to_ne_bytes(), original.time.to_ne_bytes());
+    let particles = |s: &Simulator| {
+        s.position
+            .iter()
+            .chain(s.velocity())
+            .map(|&v| bits(v))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(particles(&resumed), particles(&original));
+
+    let body = |s: &Simulator| {
+        let body = &s.rigid_bodies[0];
+        (bits(body.position), bits(body.velocity), body.rotation)
+    };
+    assert_eq!(body(&resumed), body(&original));
+    assert_eq!(
+        resumed.bodies()[0].rotation(),
+        original.bodies()[0].rotation()
+    );
+
+    let diffuse = |s: &Simulator| {
+        let particles = &s.diffuse().unwrap().particles;
+        particles
+            .iter()
+            .map(|p| bits(p.position))
+            .collect::<Vec<_>>()
+    };
+    assert!(!diffuse(&original).is_empty());
+    assert_eq!(diffuse(&resumed), diffuse(&original));
+}
+
+#[test]
+fn restoring_a_mismatched_checkpoint_keeps_the_simulator() {
+    let mut original = simulator();
+    run_steps(&mut original, 5);
+    let mut checkpoint = original.checkpoint();
+    checkpoint.state.position.pop();
+
+    let mut other = simulator();
+    let err = other.restore(checkpoint).unwrap_err();
+    assert!(matches!(
+        err,
+        CheckpointError::Mismatch {
+            what: "particles",
+            ..
+        }
+    ));
+    assert_eq!(other.time, 0.0);
+    assert_eq!(other.position.len(), other.num_sphere);
+}
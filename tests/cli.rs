@@ -0,0 +1,181 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use pbf_rs::cache::CacheReader;
+
+// a fresh output directory per test
+fn out_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("pbf_cli_{name}_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn pbf_cli(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pbf-cli"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn runs_a_builtin_scene_and_resumes_it() {
+    let out = out_dir("run");
+    let dir = out.to_str().unwrap();
+    let args = [
+        "falling block",
+        "--radius",
+        "0.04",
+        "--steps",
+        "6",
+        "--frame-every",
+        "3",
+    ];
+    let output = pbf_cli(&[&args[..], &["--checkpoint-every", "4", "--out", dir]].concat());
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    for frame in ["particles_000000", "particles_000003", "particles_000006"] {
+        let ply = fs::read_to_string(out.join("frames").join(format!("{frame}.ply"))).unwrap();
+        assert!(ply.starts_with("ply\n"));
+    }
+    let stats = fs::read_to_string(out.join("stats.csv")).unwrap();
+    // a header and the state before the first and after every step
+    assert_eq!(stats.lines().count(), 1 + 7);
+    let checkpoints = out.join("checkpoints");
+    assert!(checkpoints.join("checkpoint_000004.ron").is_file());
+    let last = checkpoints.join("checkpoint_000006.ron");
+    assert!(last.is_file());
+
+    // steps go on from the checkpoint
+    let resumed = out.join("resumed");
+    let output = pbf_cli(&[
+        "--resume",
+        last.to_str().unwrap(),
+        "--steps",
+        "3",
+        "--frame-every",
+        "3",
+        "--quiet",
+        "--out",
+        resumed.to_str().unwrap(),
+    ]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(output.stderr.is_empty());
+    assert!(resumed.join("frames/particles_000009.ply").is_file());
+    assert!(resumed.join("checkpoints/checkpoint_000009.ron").is_file());
+
+    fs::remove_dir_all(&out).unwrap();
+}
+
+#[test]
+fn particle_frames_go_to_a_cache() {
+    let out = out_dir("cache");
+    let cache = out.join("falling_block.pbfc");
+    let output = pbf_cli(&[
+        "falling block",
+        "--radius",
+        "0.04",
+        "--steps",
+        "6",
+        "--frame-every",
+        "2",
+        "--cache",
+        cache.to_str().unwrap(),
+        "--quiet",
+        "--out",
+        out.to_str().unwrap(),
+    ]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(!out.join("frames/particles_000000.ply").exists());
+
+    let reader = CacheReader::open(&cache).unwrap();
+    // the state before the first step and after every second one
+    assert_eq!(reader.len(), 4);
+    assert_eq!(reader.time(0), Some(0.0));
+    let last = reader.frame(3).unwrap();
+    assert_eq!(last.len(), reader.particles());
+    let (min, max) = reader.bounds();
+    assert!(
+        last.iter()
+            .all(|p| p.cmpge(min).all() && p.cmple(max).all())
+    );
+
+    let no_frames = pbf_cli(&[
+        "falling block",
+        "--steps",
+        "1",
+        "--frame-every",
+        "0",
+        "--cache",
+        cache.to_str().unwrap(),
+    ]);
+    assert_eq!(no_frames.status.code(), Some(2));
+    fs::remove_dir_all(&out).unwrap();
+}
+
+#[test]
+fn bad_input_exits_with_2() {
+    let out = out_dir("bad");
+    let dir = out.to_str().unwrap();
+    let missing = pbf_cli(&["no such scene", "--steps", "1", "--out", dir]);
+    assert_eq!(missing.status.code(), Some(2));
+    let no_length = pbf_cli(&["falling block", "--out", dir]);
+    assert_eq!(no_length.status.code(), Some(2));
+    let bad_radius = pbf_cli(&[
+        "falling block",
+        "--steps",
+        "1",
+        "--radius",
+        "-1",
+        "--out",
+        dir,
+    ]);
+    assert_eq!(bad_radius.status.code(), Some(2));
+    assert!(!out.exists());
+}
+
+#[test]
+fn a_blown_up_simulation_exits_with_1() {
+    let out = out_dir("blown_up");
+    fs::create_dir_all(&out).unwrap();
+    // a force field that poisons the velocities
+    let scene = out.join("blown_up.ron");
+    fs::write(
+        &scene,
+        "(
+            name: \"Blown Up\",
+            tank: (0.4, 0.4, 0.4),
+            blocks: [(size: (0.5, 0.5, 0.5), offset: (0.5, 0.0, 0.5))],
+            force_fields: [Wind(velocity: (NaN, 0.0, 0.0), drag: 1.0)],
+        )",
+    )
+    .unwrap();
+    let output = pbf_cli(&[
+        scene.to_str().unwrap(),
+        "--radius",
+        "0.04",
+        "--steps",
+        "20",
+        "--out",
+        out.to_str().unwrap(),
+    ]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{stderr}");
+    assert!(stderr.contains("non-finite"), "{stderr}");
+    // the stats lead up to the failure
+    let stats = fs::read_to_string(out.join("stats.csv")).unwrap();
+    assert_eq!(stats.lines().count(), 1 + 2);
+    fs::remove_dir_all(&out).unwrap();
+}
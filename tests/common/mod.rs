@@ -0,0 +1,56 @@
+// fixtures shared by the integration tests, every test file uses a part of them
+#![allow(dead_code)]
+
+use pbf_rs::params::SimParams;
+use pbf_rs::precision::{Real, Vector};
+use pbf_rs::scene_desc::{FluidBlock, SceneDesc};
+use pbf_rs::simulator::Simulator;
+
+// a simulator with particles of the given radius, reset to the start of the scene
+pub fn simulator(radius: Real, scene: SceneDesc) -> Simulator {
+    let params = SimParams::builder().radius(radius).build().unwrap();
+    let mut simulator = Simulator::new(params);
+    simulator.set_scene(scene);
+    simulator.reset_system();
+    simulator
+}
+
+// a block of fluid, size and offset relative to the tank as in the scene files
+pub fn fluid_block(size: Vector, offset: Vector) -> FluidBlock {
+    FluidBlock {
+        size,
+        offset,
+        material: Default::default(),
+    }
+}
+
+// a tank with one block of particles and nothing else, not even diffuse particles
+pub fn block_scene(radius: Real, tank: Vector, block: FluidBlock) -> Simulator {
+    simulator(
+        radius,
+        SceneDesc {
+            name: "Block".to_string(),
+            tank,
+            blocks: vec![block],
+            diffuse: None,
+            ..SceneDesc::default()
+        },
+    )
+}
+
+pub fn run_steps(simulator: &mut Simulator, steps: usize) {
+    let dt = simulator.params().time_step();
+    for _ in 0..steps {
+        simulator.simulate_timestep(dt);
+    }
+}
+
+pub fn run(simulator: &mut Simulator, seconds: Real) {
+    let steps = (seconds / simulator.params().time_step()).round() as usize;
+    run_steps(simulator, steps);
+}
+
+// the exact bits of a vector, to compare runs that have to be identical
+pub fn bits(v: Vector) -> Vec<u8> {
+    v.to_array().iter().flat_map(|x| x.to_ne_bytes()).collect()
+}
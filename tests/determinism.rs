@@ -0,0 +1,122 @@
+use pbf_rs::boundary::{Boundary, Shape};
+use pbf_rs::diffuse::DiffuseParams;
+use pbf_rs::material::Material;
+use pbf_rs::motion::{Motion, Track};
+use pbf_rs::precision::{Rotation, Vector, vector};
+use pbf_rs::rigid_body::{BodyShape, RigidBody};
+use pbf_rs::scene_desc::{FluidBlock, SceneDesc, SolidBlock};
+use pbf_rs::simulator::{Phase, Simulator};
+
+use crate::common::{bits, run_steps};
+
+mod common;
+
+// a bit of everything the solver does: fluid, sand, a solid, a floating box,
+// a moving collider and diffuse particles
+fn scene() -> SceneDesc {
+    SceneDesc {
+        name: "Everything".to_string(),
+        tank: vector(0.8, 0.8, 0.4),
+        blocks: vec![
+            FluidBlock {
+                size: vector(0.5, 0.6, 1.0),
+                offset: vector(0.0, 0.3, 0.5),
+                material: Material::Fluid,
+            },
+            FluidBlock {
+                size: vector(0.3, 0.3, 1.0),
+                offset: vector(1.0, 0.0, 0.5),
+                material: Material::sand(),
+            },
+        ],
+        solids: vec![SolidBlock {
+            center: vector(0.2, 0.2, 0.0),
+            half_extents: Vector::splat(0.06),
+            stiffness: 0.5,
+            friction: 0.5,
+        }],
+        rigid_bodies: vec![RigidBody::new(
+            BodyShape::Box {
+                half_extents: vector(0.08, 0.04, 0.08),
+            },
+            500.0,
+            vector(-0.2, 0.3, 0.0),
+            Rotation::IDENTITY,
+        )],
+        boundaries: vec![Boundary::new(
+            Shape::Sphere { radius: 0.06 },
+            Motion {
+                translation: Track::Sinusoid {
+                    offset: vector(0.0, -0.2, 0.0),
+                    amplitude: vector(0.2, 0.0, 0.0),
+                    frequency: 1.0,
+                    phase: 0.0,
+                },
+                rotation: Track::default(),
+            },
+        )],
+        diffuse: Some(DiffuseParams::default()),
+        ..SceneDesc::default()
+    }
+}
+
+fn run(threads: usize) -> Simulator {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    pool.install(|| {
+        let mut simulator = common::simulator(0.02, scene());
+        run_steps(&mut simulator, 150);
+        simulator
+    })
+}
+
+#[test]
+fn thread_count_does_not_change_the_result() {
+    let single = run(1);
+    let multi = run(4);
+
+    assert_eq!(single.num_sphere, multi.num_sphere);
+    for (a, b) in single.position.iter().zip(&multi.position) {
+        assert_eq!(bits(*a), bits(*b), "{a} {b}");
+    }
+
+    let body = |s: &Simulator| (bits(s.rigid_bodies[0].position), s.rigid_bodies[0].rotation);
+    assert_eq!(body(&single), body(&multi));
+
+    let diffuse = |s: &Simulator| {
+        let particles = &s.diffuse().unwrap().particles;
+        particles
+            .iter()
+            .map(|p| bits(p.position))
+            .collect::<Vec<_>>()
+    };
+    assert!(!diffuse(&single).is_empty());
+    assert_eq!(diffuse(&single), diffuse(&multi));
+}
+
+#[test]
+fn phases_run_one_at_a_time_match_whole_steps() {
+    let mut whole = common::simulator(0.02, scene());
+    let mut phased = whole.clone();
+    let dt = whole.params().time_step();
+
+    for _ in 0..5 {
+        whole.simulate_timestep(dt);
+        for phase in phased.step_phases() {
+            phased.run_phase(phase, dt);
+            let moved = phased
+                .position
+                .iter()
+                .zip(phased.predicted_position())
+                .any(|(a, b)| a != b);
+            // the predictions are committed by the velocity update
+            let committed = matches!(phase, Phase::VelocityUpdate | Phase::Diffuse);
+            assert_eq!(moved, !committed, "{}", phase.name());
+        }
+    }
+    for (a, b) in whole.position.iter().zip(&phased.position) {
+        assert_eq!(bits(*a), bits(*b), "{a} {b}");
+    }
+}
@@ -0,0 +1,105 @@
+use pbf_rs::diffuse::{DiffuseKind, DiffuseParams, DiffuseParticle, DiffuseSystem, advect};
+use pbf_rs::precision::{Real, Vector, vector};
+use pbf_rs::scene_desc::{FluidBlock, SceneDesc};
+use pbf_rs::simulator::Simulator;
+
+use crate::common::{fluid_block, run};
+
+mod common;
+
+const RADIUS: Real = 0.025;
+
+fn splash(block: FluidBlock, seconds: Real) -> Simulator {
+    let scene = SceneDesc {
+        name: "Splash".to_string(),
+        tank: vector(0.6, 1.0, 0.6),
+        blocks: vec![block],
+        diffuse: Some(DiffuseParams::default()),
+        ..SceneDesc::default()
+    };
+    let mut simulator = common::simulator(RADIUS, scene);
+    run(&mut simulator, seconds);
+    simulator
+}
+
+fn count(simulator: &Simulator, kind: DiffuseKind) -> usize {
+    let particles = &simulator.diffuse().unwrap().particles;
+    particles.iter().filter(|p| p.kind == kind).count()
+}
+
+#[test]
+fn still_water_stays_clear() {
+    let simulator = splash(fluid_block(vector(1.0, 0.3, 1.0), Vector::ZERO), 2.5);
+    // the initial lattice relaxes with a small splash, nothing is emitted once it calmed down
+    let particles = &simulator.diffuse().unwrap().particles;
+    assert!(particles.iter().all(|p| p.age > 1.0));
+}
+
+#[test]
+fn splash_makes_spray_and_foam() {
+    let simulator = splash(
+        fluid_block(vector(0.5, 0.5, 0.5), vector(0.5, 1.0, 0.5)),
+        1.0,
+    );
+    let spray = count(&simulator, DiffuseKind::Spray);
+    let foam = count(&simulator, DiffuseKind::Foam);
+    assert!(spray > 0 && foam > 0, "spray {spray} foam {foam}");
+
+    let half = 0.5 * simulator.tank;
+    for p in &simulator.diffuse().unwrap().particles {
+        assert!(
+            (p.position.abs() - half).max_element() < 0.0,
+            "{:?}",
+            p.position
+        );
+    }
+}
+
+#[test]
+fn kinds_move_differently() {
+    let params = DiffuseParams::default();
+    let gravity = vector(0.0, -9.8, 0.0);
+    let fluid_velocity = vector(0.5, 0.0, 0.0);
+    let dt = 0.01;
+    let particle = DiffuseParticle {
+        position: Vector::ZERO,
+        velocity: Vector::ZERO,
+        kind: DiffuseKind::Foam,
+        age: 0.0,
+    };
+
+    // away from the fluid it falls
+    let mut spray = particle;
+    advect(&mut spray, &params, 0.0, fluid_velocity, gravity, dt);
+    assert_eq!(spray.kind, DiffuseKind::Spray);
+    assert!(spray.velocity.y < 0.0 && spray.velocity.x == 0.0);
+
+    // on the surface it drifts with the fluid
+    let mut foam = particle;
+    advect(&mut foam, &params, 0.5, fluid_velocity, gravity, dt);
+    assert_eq!(foam.kind, DiffuseKind::Foam);
+    assert_eq!(foam.velocity, fluid_velocity);
+
+    // under water it rises
+    let mut bubble = particle;
+    advect(&mut bubble, &params, 1.0, fluid_velocity, gravity, dt);
+    assert_eq!(bubble.kind, DiffuseKind::Bubble);
+    assert!(bubble.velocity.y > 0.0 && bubble.velocity.x > 0.0);
+    assert_eq!(bubble.age, dt);
+}
+
+#[test]
+fn ply_lists_every_particle() {
+    let mut diffuse = DiffuseSystem::new(DiffuseParams::default());
+    diffuse.emit(Vector::ZERO, vector(1.0, 0.0, 0.0), 5.0, RADIUS, 0.01);
+    assert_eq!(diffuse.particles.len(), 5);
+
+    let mut out = Vec::new();
+    diffuse.write_ply(&mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let (header, body) = text.split_once("end_header\n").unwrap();
+    assert!(header.starts_with("ply\n"));
+    assert!(header.contains("element vertex 5\n"));
+    assert_eq!(body.lines().count(), 5);
+    assert!(body.lines().all(|line| line.split(' ').count() == 8));
+}
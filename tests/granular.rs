@@ -0,0 +1,112 @@
+use pbf_rs::material::Material;
+use pbf_rs::precision::{Real, vector};
+use pbf_rs::scene_desc::{FluidBlock, SceneDesc};
+use pbf_rs::simulator::Simulator;
+
+use crate::common::run;
+
+mod common;
+
+const RADIUS: Real = 0.02;
+
+// a narrow column collapsing in a thin tank
+fn collapse(materials: &[Material], seconds: Real) -> Simulator {
+    let block = |material: Material, offset_x: Real| FluidBlock {
+        size: vector(0.15, 0.9, 1.0),
+        offset: vector(offset_x, 0.0, 0.5),
+        material,
+    };
+    let blocks = match materials {
+        [material] => vec![block(*material, 0.5)],
+        _ => materials
+            .iter()
+            .enumerate()
+            .map(|(k, m)| block(*m, 0.2 + 0.6 * k as Real / (materials.len() - 1) as Real))
+            .collect(),
+    };
+    let scene = SceneDesc {
+        name: "Collapse".to_string(),
+        tank: vector(1.4, 0.6, 0.1),
+        blocks,
+        ..SceneDesc::default()
+    };
+    let mut simulator = common::simulator(RADIUS, scene);
+    run(&mut simulator, seconds);
+    simulator
+}
+
+// angle between the floor and the line from the top of the pile to its foot, in degrees
+fn repose_angle(simulator: &Simulator) -> Real {
+    let floor = -0.5 * simulator.tank.y + RADIUS;
+    let width = 2.0 * RADIUS;
+    let left = -0.5 * simulator.tank.x;
+    let num_bins = (simulator.tank.x / width).ceil() as usize;
+    let bin_of = |x: Real| (((x - left) / width) as usize).min(num_bins - 1);
+    let mut height = vec![0.0 as Real; num_bins];
+    for p in &simulator.position {
+        let bin = bin_of(p.x);
+        height[bin] = height[bin].max(p.y - floor);
+    }
+
+    let center =
+        simulator.position.iter().map(|p| p.x).sum::<Real>() / simulator.num_sphere as Real;
+    let peak = bin_of(center);
+    let top = height[peak - 1..=peak + 1].iter().sum::<Real>() / 3.0;
+
+    // the foot is where the pile thins out to a single layer
+    let covered: Vec<usize> = (0..num_bins).filter(|&bin| height[bin] >= width).collect();
+    let left_foot = center - (left + *covered.first().unwrap() as Real * width);
+    let right_foot = left + (*covered.last().unwrap() + 1) as Real * width - center;
+    (top / (0.5 * (left_foot + right_foot))).atan().to_degrees()
+}
+
+#[test]
+fn sand_piles_up() {
+    let simulator = collapse(&[Material::sand()], 2.0);
+    let angle = repose_angle(&simulator);
+    assert!(angle > 30.0 && angle < 55.0, "angle of repose {angle}");
+}
+
+#[test]
+fn rougher_sand_piles_up_steeper() {
+    let smooth = Material::Granular {
+        static_friction: 0.2,
+        kinetic_friction: 0.15,
+    };
+    let rough = Material::Granular {
+        static_friction: 0.9,
+        kinetic_friction: 0.8,
+    };
+    let smooth = repose_angle(&collapse(&[smooth], 2.0));
+    let rough = repose_angle(&collapse(&[rough], 2.0));
+    assert!(rough > smooth + 10.0, "smooth {smooth} rough {rough}");
+}
+
+#[test]
+fn fluid_does_not_pile_up() {
+    let simulator = collapse(&[Material::Fluid], 2.0);
+    let angle = repose_angle(&simulator);
+    assert!(angle < 10.0, "fluid angle {angle}");
+}
+
+#[test]
+fn sand_and_fluid_share_the_tank() {
+    let simulator = collapse(&[Material::sand(), Material::Fluid], 1.0);
+    let half = 0.5 * simulator.tank;
+    for p in &simulator.position {
+        assert!(p.is_finite());
+        assert!(p.abs().cmple(half).all());
+    }
+    // no two particles end up inside each other
+    let min = simulator
+        .position
+        .iter()
+        .enumerate()
+        .flat_map(|(i, a)| {
+            simulator.position[i + 1..]
+                .iter()
+                .map(move |b| a.distance(*b))
+        })
+        .fold(Real::MAX, Real::min);
+    assert!(min > RADIUS, "closest pair {min}");
+}
@@ -0,0 +1,174 @@
+use bevy::math::{BVec3, UVec3};
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use pbf_rs::precision::{Real, Vector, vector};
+use pbf_rs::scene_desc::SceneDesc;
+use pbf_rs::simulator::Simulator;
+
+mod common;
+
+// with the default ratio of 3 the neighbor radius is exactly 0.75
+const RADIUS: Real = 0.25;
+
+// an empty tank, the particles are placed by hand
+fn empty_tank(tank: Vector, periodic: BVec3) -> Simulator {
+    let scene = SceneDesc {
+        name: "Empty".to_string(),
+        tank,
+        periodic,
+        blocks: Vec::new(),
+        ..SceneDesc::default()
+    };
+    common::simulator(RADIUS, scene)
+}
+
+// tank size in neighbor radii, whole numbers put the walls right on the cell borders
+fn tank() -> impl Strategy<Value = Vector> {
+    let side = || {
+        (1u32..6, prop_oneof![Just(0.0), 0.0..1.0 as Real])
+            .prop_map(|(cells, fraction)| 3.0 * RADIUS * (cells as Real + fraction))
+    };
+    (side(), side(), side()).prop_map(|(x, y, z)| vector(x, y, z))
+}
+
+// a position relative to the tank, on a wall, inside or a little outside
+fn point() -> impl Strategy<Value = Vector> {
+    let coordinate = || {
+        prop_oneof![
+            1 => Just(0.0),
+            1 => Just(1.0),
+            4 => -0.1..1.1 as Real,
+        ]
+    };
+    (coordinate(), coordinate(), coordinate()).prop_map(|(x, y, z)| vector(x, y, z))
+}
+
+// scattered particles, or clusters much smaller than a cell with some particles on top of
+// each other
+fn cloud() -> impl Strategy<Value = Vec<Vector>> {
+    let offset = prop_oneof![
+        1 => Just(Vector::ZERO),
+        3 => (-0.02..0.02 as Real, -0.02..0.02 as Real, -0.02..0.02 as Real)
+            .prop_map(|(x, y, z)| vector(x, y, z)),
+    ];
+    prop_oneof![
+        vec(point(), 0..150),
+        vec((point(), vec(offset, 1..40)), 1..5).prop_map(|clusters| {
+            clusters
+                .into_iter()
+                .flat_map(|(center, offsets)| offsets.into_iter().map(move |o| center + o))
+                .collect()
+        }),
+    ]
+}
+
+fn place(tank: Vector, periodic: BVec3, cloud: &[Vector]) -> Simulator {
+    let mut simulator = empty_tank(tank, periodic);
+    simulator.position = cloud.iter().map(|&p| (p - 0.5) * tank).collect();
+    simulator.find_neighbors();
+    simulator
+}
+
+// everything within the neighbor radius, the o(n^2) way
+fn brute_force(points: &[Vector], pos: Vector, h: Real, skip: Option<usize>) -> Vec<usize> {
+    periodic_brute_force(points, pos, h, skip, |d| d)
+}
+
+// the same with the distance to the nearest periodic image
+fn periodic_brute_force(
+    points: &[Vector],
+    pos: Vector,
+    h: Real,
+    skip: Option<usize>,
+    image: impl Fn(Vector) -> Vector,
+) -> Vec<usize> {
+    (0..points.len())
+        .filter(|&j| Some(j) != skip && image(points[j] - pos).length_squared() < h * h)
+        .collect()
+}
+
+fn sorted(list: &[usize]) -> Vec<usize> {
+    let mut list = list.to_vec();
+    list.sort_unstable();
+    list
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn neighbor_lists_match_brute_force(tank in tank(), cloud in cloud()) {
+        let simulator = place(tank, BVec3::FALSE, &cloud);
+        let h = simulator.neighbor_radius();
+        let points = &simulator.position;
+        let samples = simulator.boundary_positions();
+        for (i, &p) in points.iter().enumerate() {
+            prop_assert_eq!(sorted(simulator.neighbors(i)), brute_force(points, p, h, Some(i)));
+            prop_assert_eq!(
+                sorted(simulator.boundary_neighbors(i)),
+                brute_force(samples, p, h, None)
+            );
+        }
+    }
+
+    // tanks of a single cell along a periodic axis see every particle in it only once
+    #[test]
+    fn periodic_neighbor_lists_match_brute_force(
+        tank in tank(),
+        cloud in cloud(),
+        periodic in any::<[bool; 3]>(),
+    ) {
+        let periodic = BVec3::from(periodic);
+        let simulator = place(tank, periodic, &cloud);
+        let h = simulator.neighbor_radius();
+        let points = &simulator.position;
+        let samples = simulator.boundary_positions();
+        let image = |d: Vector| Vector::select(periodic, d - tank * (d / tank).round(), d);
+        for (i, &p) in points.iter().enumerate() {
+            prop_assert_eq!(
+                sorted(simulator.neighbors(i)),
+                periodic_brute_force(points, p, h, Some(i), image)
+            );
+            prop_assert_eq!(
+                sorted(simulator.boundary_neighbors(i)),
+                periodic_brute_force(samples, p, h, None, image)
+            );
+        }
+    }
+
+    #[test]
+    fn grid_cells_hold_their_particles(tank in tank(), cloud in cloud()) {
+        let simulator = place(tank, BVec3::FALSE, &cloud);
+        let h = simulator.neighbor_radius();
+        let count = simulator.cell_count();
+        let half = 0.5 * simulator.tank;
+
+        // every particle sits in exactly one cell, the one it maps to
+        let mut cells = vec![Vec::new(); simulator.position.len()];
+        for x in 0..count.x {
+            for y in 0..count.y {
+                for z in 0..count.z {
+                    let cell = UVec3::new(x, y, z);
+                    for &i in simulator.cell_particles(cell) {
+                        cells[i].push(cell);
+                    }
+                }
+            }
+        }
+        for (i, &p) in simulator.position.iter().enumerate() {
+            let cell = simulator.cell_of(p);
+            prop_assert_eq!(&cells[i], &vec![cell]);
+            // the stencil around it stays inside the padded grid
+            prop_assert!(cell.cmpge(UVec3::ONE).all() && cell.cmple(count - 2).all());
+            // and the cell of a particle inside the tank contains it
+            if p.abs().cmple(half).all() {
+                for axis in 0..3 {
+                    let lower = (cell[axis] as Real - 1.0) * h - half[axis];
+                    let slack = 1e-4 * h;
+                    prop_assert!(p[axis] >= lower - slack && p[axis] <= lower + h + slack);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use bevy::math::BVec3;
+
+use pbf_rs::boundary::{Boundary, Shape};
+use pbf_rs::motion::{Motion, Track};
+use pbf_rs::precision::{Real, vector};
+use pbf_rs::scene_desc::SceneDesc;
+use pbf_rs::simulator::Simulator;
+
+use crate::common::{fluid_block, run};
+
+mod common;
+
+fn run_watching(simulator: &mut Simulator, seconds: Real, mut step: impl FnMut(&Simulator)) {
+    let dt = simulator.params().time_step();
+    for _ in 0..(seconds / dt).round() as usize {
+        simulator.simulate_timestep(dt);
+        step(simulator);
+    }
+}
+
+fn mean(values: impl Iterator<Item = Real>) -> Real {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    sum / count as Real
+}
+
+#[test]
+fn still_water_is_uniform_across_the_seam() {
+    let scene = SceneDesc {
+        name: "Periodic Pool".to_string(),
+        tank: vector(0.6, 0.4, 0.3),
+        periodic: BVec3::new(true, false, false),
+        blocks: vec![fluid_block(vector(1.0, 0.5, 1.0), vector(0.0, 0.0, 0.0))],
+        diffuse: None,
+        ..SceneDesc::default()
+    };
+    let mut simulator = common::simulator(0.02, scene);
+    run(&mut simulator, 1.5);
+
+    // without walls at the ends the particles next to the seam are as dense as the others,
+    // they would lack the neighbors on the other side if the search did not see across it
+    let half = 0.5 * simulator.tank.x;
+    let h = simulator.neighbor_radius();
+    let density = simulator.relative_density();
+    let at_seam = mean(
+        (0..density.len())
+            .filter(|&i| simulator.position[i].x.abs() > half - h)
+            .map(|i| density[i]),
+    );
+    let inside = mean(
+        (0..density.len())
+            .filter(|&i| simulator.position[i].x.abs() < half - 2.0 * h)
+            .map(|i| density[i]),
+    );
+    assert!(
+        (at_seam - inside).abs() < 0.05 * inside,
+        "density {at_seam} at the seam, {inside} away from it"
+    );
+}
+
+#[test]
+fn channel_flow_crosses_the_seam() {
+    let scene = SceneDesc::load("assets/scenes/channel.ron").unwrap();
+    assert_eq!(scene.periodic, BVec3::new(true, false, false));
+    let mut simulator = common::simulator(0.03, scene);
+
+    let half = 0.5 * simulator.tank;
+    let mut previous = simulator.position.clone();
+    let mut crossings = 0;
+    run_watching(&mut simulator, 2.0, |simulator| {
+        for (p, q) in simulator.position.iter().zip(&mut previous) {
+            assert!(p.abs().cmple(half).all(), "{p} left the tank");
+            // wrapped from the downstream end back to the upstream one
+            if p.x - q.x < -half.x {
+                crossings += 1;
+            }
+            *q = *p;
+        }
+    });
+    assert!(
+        crossings > 100,
+        "only {crossings} particles crossed the seam"
+    );
+
+    let density = simulator.relative_density();
+    let compression = mean(density.iter().map(|d| (d - 1.0).max(0.0)));
+    assert!(compression < 0.1, "mean compression {compression}");
+}
+
+#[test]
+fn boundaries_do_not_reach_across_the_seam() {
+    let scene = SceneDesc {
+        name: "Seam".to_string(),
+        tank: vector(0.6, 0.4, 0.3),
+        periodic: BVec3::new(true, false, false),
+        blocks: Vec::new(),
+        boundaries: vec![Boundary::new(
+            Shape::Box {
+                half_extents: vector(0.02, 0.05, 0.05),
+            },
+            Motion {
+                translation: Track::Constant(vector(0.26, 0.0, 0.0)),
+                rotation: Track::default(),
+            },
+        )],
+        diffuse: None,
+        ..SceneDesc::default()
+    };
+    let mut simulator = common::simulator(0.02, scene);
+
+    // both 0.03 from the box, which ends 0.02 before the seam, one of them on the
+    // other side of it, and away from the tank walls
+    simulator.position = vec![vector(0.21, 0.0, 0.0), vector(-0.29, 0.0, 0.0)];
+    simulator.find_neighbors();
+    assert!(!simulator.boundary_neighbors(0).is_empty());
+    assert!(simulator.boundary_neighbors(1).is_empty());
+}
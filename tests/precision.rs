@@ -0,0 +1,60 @@
+use std::fs;
+
+use pbf_rs::precision::{Real, to_f32, to_vec3, vector};
+use pbf_rs::simulator::Simulator;
+
+use crate::common::{block_scene, fluid_block, run};
+
+mod common;
+
+// positions at the end of the single precision run, rewritten by update_precision_reference
+const REFERENCE: &str = "tests/data/precision_reference.ron";
+const RADIUS: Real = 0.03;
+const SECONDS: Real = 0.2;
+
+// the start of a small dam break, the rounding differences grow exponentially in the
+// splash that follows
+// no tank side is a whole number of particle spacings or neighbor radii, there the
+// wall samples and the lattice could round to a different count in each precision
+fn dam_break() -> Simulator {
+    let block = fluid_block(vector(0.4, 0.8, 1.0), vector(0.0, 0.0, 0.5));
+    let mut simulator = block_scene(RADIUS, vector(0.8, 0.62, 0.4), block);
+    run(&mut simulator, SECONDS);
+    simulator
+}
+
+fn positions() -> Vec<[f32; 3]> {
+    dam_break()
+        .position
+        .iter()
+        .map(|&p| to_vec3(p).to_array())
+        .collect()
+}
+
+// the reference comes from the single precision build
+#[cfg(not(feature = "f64"))]
+#[test]
+#[ignore = "rewrites the reference, run it explicitly after changing the solver"]
+fn update_precision_reference() {
+    fs::write(REFERENCE, ron::to_string(&positions()).unwrap()).unwrap();
+}
+
+#[test]
+fn double_precision_follows_single_precision() {
+    let position = positions();
+    let text = fs::read_to_string(REFERENCE).unwrap();
+    let reference: Vec<[f32; 3]> = ron::from_str(&text).unwrap();
+    assert_eq!(position.len(), reference.len());
+
+    let deviation: Vec<f32> = position
+        .iter()
+        .zip(&reference)
+        .map(|(&p, &q)| bevy::math::Vec3::from(p).distance(q.into()))
+        .collect();
+    let rms = (deviation.iter().map(|d| d * d).sum::<f32>() / deviation.len() as f32).sqrt();
+    let max = deviation.iter().copied().fold(0.0, f32::max);
+    // about 1e-5 and 4e-5 apart after 0.2 s
+    let radius = to_f32(RADIUS);
+    assert!(rms < 0.01 * radius, "rms deviation {rms}");
+    assert!(max < 0.1 * radius, "max deviation {max}");
+}
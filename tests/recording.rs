@@ -0,0 +1,97 @@
+use pbf_rs::precision::{Vector, vector};
+use pbf_rs::recording::Recording;
+use pbf_rs::simulator::Simulator;
+
+use crate::common::{bits, block_scene, fluid_block, run_steps};
+
+mod common;
+
+// a dam break without diffuse particles, which keeps every frame the same size
+fn simulator() -> Simulator {
+    let block = fluid_block(vector(0.4, 0.8, 1.0), Vector::ZERO);
+    block_scene(0.03, vector(0.8, 0.6, 0.3), block)
+}
+
+// the states after each of the given number of steps, the initial one first
+fn record(simulator: &mut Simulator, steps: usize, budget: usize) -> Recording {
+    let mut recording = Recording::new(budget);
+    recording.start(simulator.scene().clone());
+    recording.push(simulator.state());
+    let dt = simulator.params().time_step();
+    for _ in 0..steps {
+        simulator.simulate_timestep(dt);
+        recording.push(simulator.state());
+    }
+    recording
+}
+
+fn all_bits(v: &[Vector]) -> Vec<u8> {
+    v.iter().flat_map(|&v| bits(v)).collect()
+}
+
+#[test]
+fn oldest_frames_are_dropped_past_the_budget() {
+    let mut simulator = simulator();
+    let unlimited = record(&mut simulator.clone(), 20, usize::MAX);
+    let frame = unlimited.bytes() / unlimited.len();
+
+    let recording = record(&mut simulator, 20, 5 * frame);
+    assert_eq!(recording.len(), 5);
+    assert!(recording.bytes() <= 5 * frame);
+    assert_eq!(recording.end_time(), unlimited.end_time());
+    assert_eq!(recording.start_time(), unlimited.get(16).map(|f| f.time));
+
+    // the newest frame stays even if it does not fit
+    let tiny = record(&mut simulator, 3, 1);
+    assert_eq!(tiny.len(), 1);
+    assert_eq!(tiny.end_time(), Some(simulator.time));
+}
+
+#[test]
+fn frames_are_found_by_time() {
+    let mut simulator = simulator();
+    let mut recording = record(&mut simulator, 10, usize::MAX);
+    let dt = simulator.params().time_step();
+    assert_eq!(recording.index_at(-1.0), Some(0));
+    assert_eq!(recording.index_at(0.0), Some(0));
+    assert_eq!(recording.index_at(3.5 * dt), Some(3));
+    assert_eq!(recording.index_at(100.0), Some(10));
+
+    let bytes = recording.bytes();
+    recording.truncate(4);
+    assert_eq!(recording.len(), 4);
+    assert!(recording.bytes() < bytes);
+    assert_eq!(recording.index_at(100.0), Some(3));
+    recording.clear();
+    assert_eq!((recording.index_at(0.0), recording.bytes()), (None, 0));
+    assert!(recording.scene().is_none());
+}
+
+#[test]
+fn stepping_on_from_a_recorded_frame_repeats_the_run() {
+    let mut simulator = simulator();
+    let recording = record(&mut simulator, 30, usize::MAX);
+
+    simulator.set_state(recording.get(10).unwrap()).unwrap();
+    run_steps(&mut simulator, 20);
+    let last = recording.last().unwrap();
+    assert_eq!(simulator.time.to_ne_bytes(), last.time.to_ne_bytes());
+    assert_eq!(all_bits(&simulator.position), all_bits(&last.position));
+    assert_eq!(all_bits(simulator.velocity()), all_bits(&last.velocity));
+}
+
+#[test]
+fn a_recorded_frame_restores_in_another_simulator() {
+    let mut simulator = simulator();
+    let recording = record(&mut simulator, 30, usize::MAX);
+    assert_eq!(
+        recording.scene().map(|scene| &scene.name),
+        Some(&simulator.scene().name)
+    );
+
+    let mut other = Simulator::new(simulator.params().clone());
+    other.restore(recording.checkpoint(10).unwrap()).unwrap();
+    run_steps(&mut other, 20);
+    assert_eq!(all_bits(&other.position), all_bits(&simulator.position));
+    assert!(recording.checkpoint(31).is_none());
+}
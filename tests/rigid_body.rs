@@ -0,0 +1,98 @@
+use pbf_rs::precision::{Real, Rotation, Vector, vector};
+use pbf_rs::rigid_body::{BodyShape, RigidBody};
+use pbf_rs::scene_desc::SceneDesc;
+use pbf_rs::simulator::Simulator;
+
+use crate::common::{fluid_block, run};
+
+mod common;
+
+const RADIUS: Real = 0.02;
+const HALF: Real = 0.07;
+
+fn cube(density: Real, x: Real) -> RigidBody {
+    RigidBody::new(
+        BodyShape::Box {
+            half_extents: Vector::splat(HALF),
+        },
+        density,
+        vector(x, 0.05, 0.0),
+        Rotation::IDENTITY,
+    )
+}
+
+// floating cubes tip over onto an edge, balls keep their waterline
+fn ball(density: Real, x: Real) -> RigidBody {
+    RigidBody::new(
+        BodyShape::Sphere { radius: HALF },
+        density,
+        vector(x, 0.05, 0.0),
+        Rotation::IDENTITY,
+    )
+}
+
+// drop the bodies into a pool and return the simulator once they settled
+// along with their mean heights over the last half second
+fn drop_into_pool(tank: Vector, rigid_bodies: Vec<RigidBody>) -> (Simulator, Vec<Real>) {
+    let scene = SceneDesc {
+        name: "Pool".to_string(),
+        tank,
+        blocks: vec![fluid_block(vector(1.0, 0.5, 1.0), Vector::ZERO)],
+        rigid_bodies,
+        ..SceneDesc::default()
+    };
+    let mut simulator = common::simulator(RADIUS, scene);
+    run(&mut simulator, 2.0);
+    let dt = simulator.params().time_step();
+    let steps = (0.5 / dt).round() as usize;
+    let mut heights = vec![0.0; simulator.rigid_bodies.len()];
+    for _ in 0..steps {
+        simulator.simulate_timestep(dt);
+        for (height, body) in heights.iter_mut().zip(&simulator.rigid_bodies) {
+            *height += body.position.y / steps as Real;
+        }
+    }
+    (simulator, heights)
+}
+
+// height of the free surface, a particle reaches one radius above its center
+fn water_level(simulator: &Simulator) -> Real {
+    let mut heights: Vec<Real> = simulator
+        .position
+        .iter()
+        .filter(|p| p.x.abs() < 0.05)
+        .map(|p| p.y)
+        .collect();
+    heights.sort_by(|a, b| a.total_cmp(b));
+    heights[heights.len() * 98 / 100] + RADIUS
+}
+
+#[test]
+fn light_bodies_float() {
+    let (simulator, heights) = drop_into_pool(
+        vector(0.8, 0.6, 0.4),
+        vec![ball(250.0, -0.2), ball(750.0, 0.2)],
+    );
+    let level = water_level(&simulator);
+    for &height in &heights {
+        let top = height + HALF;
+        let bottom = height - HALF;
+        assert!(top > level + 0.01, "top {top} level {level}");
+        assert!(bottom < level - 0.01, "bottom {bottom} level {level}");
+    }
+
+    let (light, heavy) = (heights[0], heights[1]);
+    assert!(light > heavy + 0.02, "light {light} heavy {heavy}");
+}
+
+#[test]
+fn heavy_body_sinks() {
+    let (simulator, heights) = drop_into_pool(vector(0.4, 0.6, 0.4), vec![cube(3000.0, 0.0)]);
+    let floor = -0.5 * simulator.tank.y;
+    let bottom = heights[0] - HALF;
+    // at most a layer of particles stays trapped underneath
+    assert!(
+        bottom < floor + 2.0 * RADIUS,
+        "bottom {bottom} floor {floor}"
+    );
+}
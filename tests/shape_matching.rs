@@ -0,0 +1,103 @@
+use pbf_rs::precision::{Real, Vector, vector};
+use pbf_rs::scene_desc::{SceneDesc, SolidBlock};
+use pbf_rs::simulator::Simulator;
+
+use crate::common::{fluid_block, run};
+
+mod common;
+
+const RADIUS: Real = 0.02;
+
+fn drop_cube(stiffness: Real, pool: bool) -> (Simulator, Vec<Vector>) {
+    let blocks = if pool {
+        vec![fluid_block(vector(1.0, 0.3, 1.0), Vector::ZERO)]
+    } else {
+        Vec::new()
+    };
+    let scene = SceneDesc {
+        name: "Drop".to_string(),
+        tank: vector(0.6, 0.8, 0.6),
+        blocks,
+        solids: vec![SolidBlock {
+            center: vector(0.0, 0.2, 0.0),
+            half_extents: Vector::splat(0.1),
+            stiffness,
+            friction: 0.5,
+        }],
+        ..SceneDesc::default()
+    };
+    let simulator = common::simulator(RADIUS, scene);
+    let rest = simulator.position.clone();
+    (simulator, rest)
+}
+
+// largest relative change of a distance between two particles of the body
+fn max_strain(simulator: &Simulator, rest: &[Vector]) -> Real {
+    let solid: Vec<usize> = (0..simulator.num_sphere)
+        .filter(|&i| simulator.body[i].is_some())
+        .collect();
+    let mut strain: Real = 0.0;
+    for (a, &i) in solid.iter().enumerate() {
+        for &j in &solid[a + 1..] {
+            let d0 = rest[i].distance(rest[j]);
+            let d = simulator.position[i].distance(simulator.position[j]);
+            strain = strain.max((d - d0).abs() / d0);
+        }
+    }
+    strain
+}
+
+#[test]
+fn rigid_cube_keeps_its_shape() {
+    let (mut simulator, rest) = drop_cube(1.0, false);
+    let mut strain: Real = 0.0;
+    for _ in 0..20 {
+        run(&mut simulator, 0.05);
+        strain = strain.max(max_strain(&simulator, &rest));
+    }
+    assert!(strain < 0.05, "strain {strain}");
+
+    // it came to rest on the floor
+    let lowest = simulator
+        .position
+        .iter()
+        .map(|p| p.y)
+        .fold(Real::MAX, Real::min);
+    assert!((lowest - (-0.4 + RADIUS)).abs() < 0.01, "lowest {lowest}");
+}
+
+#[test]
+fn soft_cube_deforms_more_than_a_rigid_one() {
+    let strain = |stiffness| {
+        let (mut simulator, rest) = drop_cube(stiffness, false);
+        let mut strain: Real = 0.0;
+        for _ in 0..20 {
+            run(&mut simulator, 0.02);
+            strain = strain.max(max_strain(&simulator, &rest));
+        }
+        strain
+    };
+    let soft = strain(0.05);
+    let rigid = strain(1.0);
+    assert!(soft > 2.0 * rigid, "soft {soft} rigid {rigid}");
+}
+
+#[test]
+fn fluid_stays_out_of_the_cube() {
+    let (mut simulator, _) = drop_cube(1.0, true);
+    run(&mut simulator, 1.0);
+    let (solid, fluid): (Vec<usize>, Vec<usize>) =
+        (0..simulator.num_sphere).partition(|&i| simulator.body[i].is_some());
+
+    // the box spanned by the outermost particle centers, in the frame of the cube
+    let body = &simulator.bodies()[0];
+    let center = solid.iter().map(|&i| simulator.position[i]).sum::<Vector>() / solid.len() as Real;
+    let half = Vector::splat(0.1 - RADIUS);
+    for &j in &fluid {
+        let p = body.rotation().inverse() * (simulator.position[j] - center);
+        assert!(
+            p.abs().cmpge(half).any(),
+            "fluid particle {j} at {p} inside the cube"
+        );
+    }
+}
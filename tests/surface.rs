@@ -0,0 +1,101 @@
+use pbf_rs::boundary::{Boundary, Shape, SlipMode, Surface};
+use pbf_rs::motion::Motion;
+use pbf_rs::precision::{Real, Vector, vector};
+use pbf_rs::scene_desc::{FluidBlock, SceneDesc};
+use pbf_rs::simulator::Simulator;
+
+use crate::common::{fluid_block, run_steps};
+
+mod common;
+
+const RADIUS: Real = 0.02;
+
+// a block of fluid above a plane boundary with the given surface
+fn above_plane(tank: Vector, block: FluidBlock, plane: Boundary) -> Simulator {
+    let scene = SceneDesc {
+        name: "Plane".to_string(),
+        tank,
+        blocks: vec![block],
+        boundaries: vec![plane],
+        diffuse: None,
+        ..SceneDesc::default()
+    };
+    common::simulator(RADIUS, scene)
+}
+
+// mean velocity of the particles along a direction after every step
+fn mean_velocity(simulator: &mut Simulator, direction: Vector, steps: usize) -> Vec<Real> {
+    (0..steps)
+        .map(|_| {
+            run_steps(simulator, 1);
+            let velocity = simulator.velocity();
+            velocity.iter().map(|v| v.dot(direction)).sum::<Real>() / velocity.len() as Real
+        })
+        .collect()
+}
+
+// a layer one particle thick falling onto a floor, returns the mean vertical velocity at
+// the impact, the largest one after it and the last one
+fn drop_layer(restitution: Real) -> (Real, Real, Real) {
+    let floor = Boundary::new(
+        Shape::Plane { normal: Vector::Y },
+        Motion::fixed(vector(0.0, -0.3, 0.0)),
+    )
+    .with_surface(Surface {
+        restitution,
+        ..Surface::default()
+    });
+    let block = fluid_block(vector(0.5, 0.04, 0.5), vector(0.5, 0.8, 0.5));
+    let mut simulator = above_plane(vector(0.4, 1.0, 0.4), block, floor);
+    let velocity = mean_velocity(&mut simulator, Vector::Y, 200);
+    let impact = (0..velocity.len())
+        .min_by(|&a, &b| velocity[a].total_cmp(&velocity[b]))
+        .unwrap();
+    let rebound = velocity[impact..]
+        .iter()
+        .copied()
+        .fold(Real::MIN, Real::max);
+    (velocity[impact], rebound, velocity[velocity.len() - 1])
+}
+
+#[test]
+fn restitution_sets_the_rebound() {
+    let (impact, rebound, last) = drop_layer(0.0);
+    assert!(impact < -2.0, "impact {impact}");
+    assert!(rebound < 0.1 * -impact, "impact {impact} rebound {rebound}");
+    assert!(last.abs() < 0.05, "still moving at {last}");
+
+    let (impact, rebound, _) = drop_layer(0.95);
+    assert!(rebound > 0.3 * -impact, "impact {impact} rebound {rebound}");
+}
+
+// water sliding down an inclined plane, returns the mean velocity down the slope
+fn slide(surface: Surface) -> Real {
+    let angle: Real = 0.35;
+    let chute = Boundary::new(
+        Shape::Plane {
+            normal: vector(angle.sin(), angle.cos(), 0.0),
+        },
+        Motion::fixed(vector(0.0, -0.3, 0.0)),
+    )
+    .with_surface(surface);
+    let block = fluid_block(vector(0.15, 0.2, 1.0), vector(0.05, 0.75, 0.5));
+    let mut simulator = above_plane(vector(2.0, 1.0, 0.3), block, chute);
+    let downhill = vector(angle.cos(), -angle.sin(), 0.0);
+    *mean_velocity(&mut simulator, downhill, 100).last().unwrap()
+}
+
+#[test]
+fn rough_chutes_are_slower() {
+    let smooth = slide(Surface::default());
+    let rough = slide(Surface {
+        friction: 1.0,
+        ..Surface::default()
+    });
+    let sticky = slide(Surface {
+        slip: SlipMode::NoSlip,
+        ..Surface::default()
+    });
+    assert!(rough < 0.85 * smooth, "rough {rough} smooth {smooth}");
+    assert!(sticky < rough, "no slip {sticky} rough {rough}");
+}
@@ -0,0 +1,128 @@
+use pbf_rs::precision::{Real, Vector, vector};
+use pbf_rs::simulator::Simulator;
+
+use crate::common::{block_scene, fluid_block, run};
+
+mod common;
+
+// Martin & Moyce 1952, collapse of a column twice as high as wide (n^2 = 2)
+// front position Z = x / a over time T = t sqrt(2 g / a), a the initial column width
+const MARTIN_MOYCE: [(Real, Real); 14] = [
+    (0.41, 1.11),
+    (0.84, 1.22),
+    (1.19, 1.44),
+    (1.43, 1.67),
+    (1.63, 1.89),
+    (1.83, 2.11),
+    (1.98, 2.33),
+    (2.20, 2.56),
+    (2.32, 2.78),
+    (2.51, 3.00),
+    (2.65, 3.22),
+    (2.83, 3.44),
+    (2.98, 3.67),
+    (3.11, 3.89),
+];
+
+// value below which the given fraction of the particle coordinates lie
+fn percentile(simulator: &Simulator, axis: usize, fraction: Real) -> Real {
+    let mut values: Vec<Real> = simulator.position.iter().map(|p| p[axis]).collect();
+    values.sort_by(|a, b| a.total_cmp(b));
+    values[((values.len() - 1) as Real * fraction) as usize]
+}
+
+// mean amount by which the particles are compressed beyond the rest density
+fn compression(simulator: &Simulator) -> Real {
+    let density = simulator.relative_density();
+    density.iter().map(|d| (d - 1.0).max(0.0)).sum::<Real>() / density.len() as Real
+}
+
+#[test]
+fn resting_column_is_hydrostatic() {
+    let radius = 0.02;
+    let block = fluid_block(vector(1.0, 0.6, 1.0), Vector::ZERO);
+    let mut simulator = block_scene(radius, vector(0.3, 0.8, 0.3), block);
+    let floor = -0.5 * simulator.tank.y;
+    let level = |simulator: &Simulator| percentile(simulator, 1, 0.98) + radius - floor;
+    let initial = level(&simulator);
+    let count = simulator.num_sphere;
+
+    run(&mut simulator, 1.0);
+    let before = simulator.position.clone();
+    run(&mut simulator, 0.5);
+
+    // no particle is lost and the volume stays the same
+    assert_eq!(simulator.num_sphere, count);
+    let half = 0.5 * simulator.tank;
+    assert!(simulator.position.iter().all(|p| p.abs().cmple(half).all()));
+    let level = level(&simulator);
+    assert!(
+        (level / initial - 1.0).abs() < 0.02,
+        "level {level} initial {initial}"
+    );
+
+    // at rest, the particles only jitter in place
+    let drift = simulator
+        .position
+        .iter()
+        .zip(&before)
+        .map(|(p, q)| (*p - *q).length())
+        .sum::<Real>()
+        / count as Real;
+    assert!(drift < radius, "mean drift {drift}");
+
+    // the fluid carries its own weight, so it is compressed more the deeper it is
+    let density = simulator.relative_density();
+    let layer = |depth: Real| {
+        let inside = |p: &Vector| {
+            let d = level - (p.y - floor);
+            p.x.abs() < 0.1 && p.z.abs() < 0.1 && (d - depth).abs() < 0.05
+        };
+        let values: Vec<Real> = simulator
+            .position
+            .iter()
+            .zip(&density)
+            .filter(|(p, _)| inside(p))
+            .map(|(_, d)| *d)
+            .collect();
+        values.iter().sum::<Real>() / values.len() as Real
+    };
+    let (top, middle, bottom) = (layer(0.1), layer(0.2), layer(0.35));
+    assert!(
+        top < middle && middle < bottom,
+        "top {top} middle {middle} bottom {bottom}"
+    );
+    assert!(compression(&simulator) < 0.03);
+}
+
+#[test]
+fn dam_break_follows_martin_moyce() {
+    let radius = 0.0075;
+    let block = fluid_block(vector(0.2, 0.84, 1.0), vector(0.0, 0.0, 0.5));
+    let mut simulator = block_scene(radius, vector(1.0, 0.5, 0.1), block);
+    let wall = -0.5 * simulator.tank.x;
+    // the leading edge, ignoring the odd particle splashing ahead
+    let front = |simulator: &Simulator| percentile(simulator, 0, 0.99) + radius - wall;
+    let a = front(&simulator);
+    let height = percentile(&simulator, 1, 1.0) + radius + 0.5 * simulator.tank.y;
+    assert!((height / a - 2.0).abs() < 0.1, "width {a} height {height}");
+
+    let dt = simulator.params().time_step();
+    let g = simulator.params().gravity().length();
+    let mut worst: Real = 0.0;
+    for &(t, expected) in &MARTIN_MOYCE {
+        while simulator.time * (2.0 * g / a).sqrt() < t {
+            simulator.simulate_timestep(dt);
+            // the initial particle lattice needs a few steps to relax
+            if simulator.time > 0.05 {
+                worst = worst.max(compression(&simulator));
+            }
+        }
+        let z = front(&simulator) / a;
+        assert!(
+            (z / expected - 1.0).abs() < 0.15,
+            "T {t}: front {z}, measured {expected}"
+        );
+    }
+    assert!(worst < 0.1, "compression {worst}");
+}